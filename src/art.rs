@@ -0,0 +1,180 @@
+use crate::models::{ArtPalette, OverlayConfig, SongInfo};
+use base64::{Engine as _, engine::general_purpose};
+use sha2::{Digest, Sha256};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, OnceLock};
+
+mod fetch;
+mod normalize;
+mod palette;
+
+pub use fetch::{ArtFetcher, FetchLimits, default_cache_dir, is_remote};
+use normalize::normalize;
+
+// a few tracks back, so overlays still loading the previous cover don't 404
+const MAX_ENTRIES: usize = 32;
+
+const ART_ROUTE: &str = "/api/art";
+
+pub struct StoredArt {
+    pub hash: String,
+    pub bytes: Arc<Vec<u8>>,
+    pub content_type: &'static str,
+    // only built for the inline_album_art compatibility flag
+    base64: OnceLock<Arc<String>>,
+}
+
+impl StoredArt {
+    fn new(bytes: Arc<Vec<u8>>, content_type: &'static str) -> Arc<Self> {
+        Arc::new(StoredArt {
+            hash: content_hash(&bytes),
+            bytes,
+            content_type,
+            base64: OnceLock::new(),
+        })
+    }
+
+    pub fn url(&self) -> String {
+        format!("{ART_ROUTE}/{}", self.hash)
+    }
+
+    pub fn base64(&self) -> Arc<String> {
+        self.base64
+            .get_or_init(|| Arc::new(general_purpose::STANDARD.encode(self.bytes.as_slice())))
+            .clone()
+    }
+}
+
+// longest side in px of what gets served
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArtSizes {
+    pub thumbnail_px: u32,
+    // for the blurred ambient background, can be tiny
+    pub background_px: u32,
+}
+
+impl ArtSizes {
+    pub fn from_config(config: &OverlayConfig) -> Self {
+        ArtSizes {
+            thumbnail_px: config.art_thumbnail_px,
+            background_px: config.art_background_px,
+        }
+    }
+}
+
+// a cover from a reader and the variants made from it
+pub struct PublishedArt {
+    source: Arc<Vec<u8>>,
+    sizes: ArtSizes,
+    pub thumbnail: Arc<StoredArt>,
+    pub background: Arc<StoredArt>,
+    pub palette: Option<ArtPalette>,
+}
+
+impl PublishedArt {
+    fn new(source: Arc<Vec<u8>>, sizes: ArtSizes) -> Self {
+        let (thumbnail, background, palette) =
+            match normalize(&source, &[sizes.thumbnail_px, sizes.background_px]) {
+                Some(normalized) => {
+                    let mut stored = normalized
+                        .variants
+                        .into_iter()
+                        .map(|v| StoredArt::new(Arc::new(v.bytes), v.content_type));
+                    (
+                        stored.next().unwrap(),
+                        stored.next().unwrap(),
+                        normalized.palette,
+                    )
+                }
+                // can't decode it, pass it on as is and let the browser try
+                None => {
+                    let original = StoredArt::new(source.clone(), content_type(&source));
+                    (original.clone(), original, None)
+                }
+            };
+        PublishedArt {
+            source,
+            sizes,
+            thumbnail,
+            background,
+            palette,
+        }
+    }
+
+    fn is_from(&self, source: &Arc<Vec<u8>>, sizes: ArtSizes) -> bool {
+        // readers hand out the same Arc while a track plays, skip comparing bytes then
+        self.sizes == sizes && (Arc::ptr_eq(&self.source, source) || self.source == *source)
+    }
+}
+
+// album art variants by content hash, most recently used last
+#[derive(Default)]
+pub struct ArtStore {
+    published: Mutex<VecDeque<Arc<PublishedArt>>>,
+}
+
+impl ArtStore {
+    // decodes and scales a cover the first time it's seen
+    pub fn publish(&self, source: Arc<Vec<u8>>, sizes: ArtSizes) -> Arc<PublishedArt> {
+        let mut published = self.published.lock().unwrap();
+        if let Some(index) = published.iter().position(|p| p.is_from(&source, sizes)) {
+            let art = published.remove(index).unwrap();
+            published.push_back(art.clone());
+            return art;
+        }
+        // only the reader thread publishes, don't block /api/art while decoding
+        drop(published);
+        let art = Arc::new(PublishedArt::new(source, sizes));
+
+        let mut published = self.published.lock().unwrap();
+        published.push_back(art.clone());
+        if published.len() > MAX_ENTRIES {
+            published.pop_front();
+        }
+        art
+    }
+
+    pub fn get(&self, hash: &str) -> Option<Arc<StoredArt>> {
+        let published = self.published.lock().unwrap();
+        published
+            .iter()
+            .flat_map(|p| [&p.thumbnail, &p.background])
+            .find(|art| art.hash == hash)
+            .cloned()
+    }
+
+    // swaps the raw art the reader found for urls (and base64 if asked to)
+    pub fn attach(&self, song: &mut SongInfo, config: &OverlayConfig) {
+        let Some(source) = song.album_art.clone() else {
+            song.album_art_url = None;
+            song.album_art_background_url = None;
+            song.album_art_mime = None;
+            song.album_art_palette = None;
+            song.album_art_base64 = None;
+            return;
+        };
+        let art = self.publish(source, ArtSizes::from_config(config));
+        song.album_art_url = Some(art.thumbnail.url());
+        song.album_art_background_url = Some(art.background.url());
+        song.album_art_mime = Some(art.thumbnail.content_type.to_string());
+        song.album_art_palette = art.palette.clone();
+        song.album_art_base64 = config.inline_album_art.then(|| art.thumbnail.base64());
+    }
+}
+
+// sha256 as hex
+pub fn content_hash(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+fn content_type(bytes: &[u8]) -> &'static str {
+    image::guess_format(bytes)
+        .map(|format| format.to_mime_type())
+        .unwrap_or("application/octet-stream")
+}
+
+#[cfg(test)]
+mod tests;
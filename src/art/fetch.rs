@@ -0,0 +1,227 @@
+use crate::config::{APP_DIR, write_atomic};
+use reqwest::StatusCode;
+use std::collections::{HashSet, VecDeque};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+
+// remembered results, failures too so a broken url isn't retried every poll
+const MAX_REMEMBERED: usize = 64;
+const MAX_CACHE_FILES: usize = 256;
+const CACHE_DIR: &str = "art";
+
+#[derive(Debug, Clone, Copy)]
+pub struct FetchLimits {
+    pub connect_timeout: Duration,
+    pub timeout: Duration,
+    pub max_bytes: usize,
+}
+
+impl Default for FetchLimits {
+    fn default() -> Self {
+        FetchLimits {
+            connect_timeout: Duration::from_secs(5),
+            timeout: Duration::from_secs(15),
+            max_bytes: 8 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FetchError {
+    Request(reqwest::Error),
+    Status(StatusCode),
+    TooLarge,
+    NotAnImage,
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FetchError::Request(e) => write!(f, "request failed: {e}"),
+            FetchError::Status(status) => write!(f, "server answered {status}"),
+            FetchError::TooLarge => write!(f, "image too large"),
+            FetchError::NotAnImage => write!(f, "not an image"),
+        }
+    }
+}
+
+impl std::error::Error for FetchError {}
+
+impl From<reqwest::Error> for FetchError {
+    fn from(e: reqwest::Error) -> Self {
+        FetchError::Request(e)
+    }
+}
+
+#[derive(Default)]
+struct FetchState {
+    done: VecDeque<(String, Option<Arc<Vec<u8>>>)>,
+    pending: HashSet<String>,
+}
+
+impl FetchState {
+    fn remember(&mut self, url: String, art: Option<Arc<Vec<u8>>>) {
+        self.pending.remove(&url);
+        self.done.retain(|(u, _)| *u != url);
+        self.done.push_back((url, art));
+        if self.done.len() > MAX_REMEMBERED {
+            self.done.pop_front();
+        }
+    }
+}
+
+// downloads http(s) art in the background for the (blocking) reader thread
+#[derive(Clone)]
+pub struct ArtFetcher {
+    client: reqwest::Client,
+    limits: FetchLimits,
+    // on-disk cache keyed by url, None to keep it in memory only
+    cache_dir: Option<PathBuf>,
+    runtime: Handle,
+    state: Arc<Mutex<FetchState>>,
+}
+
+pub fn is_remote(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+// <platform cache dir>/currentsong/art (~/.cache on linux, %LOCALAPPDATA% on windows)
+pub fn default_cache_dir() -> Option<PathBuf> {
+    dirs::cache_dir().map(|dir| dir.join(APP_DIR).join(CACHE_DIR))
+}
+
+impl ArtFetcher {
+    // must be called inside the tokio runtime the downloads should run on
+    pub fn new(cache_dir: Option<PathBuf>, limits: FetchLimits) -> Self {
+        let client = reqwest::Client::builder()
+            .connect_timeout(limits.connect_timeout)
+            .timeout(limits.timeout)
+            .user_agent(concat!("currentsong/", env!("CARGO_PKG_VERSION")))
+            .build()
+            .expect("http client");
+        ArtFetcher {
+            client,
+            limits,
+            cache_dir,
+            runtime: Handle::current(),
+            state: Arc::new(Mutex::new(FetchState::default())),
+        }
+    }
+
+    // the art if it's been fetched, otherwise starts fetching and calls `on_ready` when done
+    pub fn lookup(
+        &self,
+        url: &str,
+        on_ready: impl FnOnce() + Send + 'static,
+    ) -> Option<Arc<Vec<u8>>> {
+        if !is_remote(url) {
+            return None;
+        }
+        let mut state = self.state.lock().unwrap();
+        if let Some((_, art)) = state.done.iter().find(|(u, _)| u == url) {
+            return art.clone();
+        }
+        if state.pending.contains(url) {
+            return None;
+        }
+        if let Some(bytes) = self.read_cache(url) {
+            let art = Arc::new(bytes);
+            state.remember(url.to_string(), Some(art.clone()));
+            return Some(art);
+        }
+
+        state.pending.insert(url.to_string());
+        let fetcher = self.clone();
+        let url = url.to_string();
+        self.runtime.spawn(async move {
+            let art = match fetcher.fetch(&url).await {
+                Ok(bytes) => {
+                    fetcher.write_cache(&url, &bytes);
+                    Some(Arc::new(bytes))
+                }
+                Err(e) => {
+                    eprintln!("could not fetch album art {url}: {e}");
+                    None
+                }
+            };
+            fetcher.state.lock().unwrap().remember(url, art);
+            on_ready();
+        });
+        None
+    }
+
+    pub async fn fetch(&self, url: &str) -> Result<Vec<u8>, FetchError> {
+        let mut response = self.client.get(url).send().await?;
+        if !response.status().is_success() {
+            return Err(FetchError::Status(response.status()));
+        }
+        if response
+            .content_length()
+            .is_some_and(|len| len > self.limits.max_bytes as u64)
+        {
+            return Err(FetchError::TooLarge);
+        }
+
+        // content-length can be missing or wrong, count what actually arrives
+        let mut bytes = Vec::new();
+        while let Some(chunk) = response.chunk().await? {
+            if bytes.len() + chunk.len() > self.limits.max_bytes {
+                return Err(FetchError::TooLarge);
+            }
+            bytes.extend_from_slice(&chunk);
+        }
+
+        // servers (and players) get the content type wrong, trust the bytes
+        image::guess_format(&bytes).map_err(|_| FetchError::NotAnImage)?;
+        Ok(bytes)
+    }
+
+    fn cache_path(&self, url: &str) -> Option<PathBuf> {
+        let dir = self.cache_dir.as_ref()?;
+        Some(dir.join(super::content_hash(url.as_bytes())))
+    }
+
+    fn read_cache(&self, url: &str) -> Option<Vec<u8>> {
+        std::fs::read(self.cache_path(url)?).ok()
+    }
+
+    fn write_cache(&self, url: &str, bytes: &[u8]) {
+        let Some(path) = self.cache_path(url) else {
+            return;
+        };
+        if let Err(e) = write_atomic(&path, bytes) {
+            eprintln!("could not cache album art at {}: {e}", path.display());
+            return;
+        }
+        if let Some(dir) = path.parent() {
+            prune_cache(dir, MAX_CACHE_FILES);
+        }
+    }
+}
+
+// drops the least recently written files beyond `max_files`
+fn prune_cache(dir: &Path, max_files: usize) {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return;
+    };
+    let mut files: Vec<_> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let modified = entry.metadata().ok()?.modified().ok()?;
+            Some((modified, entry.path()))
+        })
+        .collect();
+    if files.len() <= max_files {
+        return;
+    }
+    files.sort();
+    for (_, path) in &files[..files.len() - max_files] {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(test)]
+mod tests;
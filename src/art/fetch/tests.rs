@@ -0,0 +1,158 @@
+use super::{ArtFetcher, FetchError, FetchLimits, prune_cache};
+use axum::{Router, body::Body, http::header, response::IntoResponse, routing::get};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+struct StandIn {
+    base: String,
+    hits: Arc<AtomicUsize>,
+}
+
+// serves a few canned responses on an ephemeral port
+async fn stand_in() -> StandIn {
+    let hits = Arc::new(AtomicUsize::new(0));
+    let counter = hits.clone();
+    let app = Router::new()
+        .route(
+            "/cover.png",
+            get(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                async { ([(header::CONTENT_TYPE, "image/png")], PNG) }
+            }),
+        )
+        // wrong content type, the bytes are still a png
+        .route(
+            "/mislabeled",
+            get(|| async { ([(header::CONTENT_TYPE, "text/plain")], PNG) }),
+        )
+        .route("/page.html", get(|| async { "<html></html>" }))
+        .route("/big", get(|| async { vec![0u8; 4096] }))
+        .route(
+            "/big-chunked",
+            get(|| async {
+                let chunks = (0..8).map(|_| Ok::<_, std::io::Error>(vec![0u8; 1024]));
+                Body::from_stream(futures::stream::iter(chunks)).into_response()
+            }),
+        )
+        .route(
+            "/slow",
+            get(|| async {
+                tokio::time::sleep(Duration::from_secs(5)).await;
+                PNG
+            }),
+        );
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let base = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(listener, app).into_future());
+    StandIn { base, hits }
+}
+
+fn limits() -> FetchLimits {
+    FetchLimits {
+        connect_timeout: Duration::from_secs(1),
+        timeout: Duration::from_millis(300),
+        max_bytes: 2048,
+    }
+}
+
+#[tokio::test]
+async fn fetches_images_and_sniffs_the_bytes() {
+    let server = stand_in().await;
+    let fetcher = ArtFetcher::new(None, limits());
+
+    let bytes = fetcher.fetch(&format!("{}/cover.png", server.base)).await;
+    assert_eq!(bytes.unwrap(), PNG);
+    let bytes = fetcher.fetch(&format!("{}/mislabeled", server.base)).await;
+    assert_eq!(bytes.unwrap(), PNG);
+
+    let page = fetcher.fetch(&format!("{}/page.html", server.base)).await;
+    assert!(matches!(page, Err(FetchError::NotAnImage)));
+}
+
+#[tokio::test]
+async fn rejects_errors_oversized_and_slow_responses() {
+    let server = stand_in().await;
+    let fetcher = ArtFetcher::new(None, limits());
+
+    let missing = fetcher.fetch(&format!("{}/missing", server.base)).await;
+    assert!(matches!(missing, Err(FetchError::Status(status)) if status == 404));
+    let big = fetcher.fetch(&format!("{}/big", server.base)).await;
+    assert!(matches!(big, Err(FetchError::TooLarge)));
+    let chunked = fetcher.fetch(&format!("{}/big-chunked", server.base)).await;
+    assert!(matches!(chunked, Err(FetchError::TooLarge)));
+    let slow = fetcher.fetch(&format!("{}/slow", server.base)).await;
+    assert!(matches!(slow, Err(FetchError::Request(e)) if e.is_timeout()));
+}
+
+#[tokio::test]
+async fn lookup_fetches_once_in_the_background() {
+    let server = stand_in().await;
+    let fetcher = ArtFetcher::new(None, limits());
+    let url = format!("{}/cover.png", server.base);
+
+    let (ready_tx, ready_rx) = oneshot::channel();
+    assert_eq!(
+        fetcher.lookup(&url, move || ready_tx.send(()).unwrap()),
+        None
+    );
+    // already on its way
+    assert_eq!(fetcher.lookup(&url, || panic!("fetched twice")), None);
+    ready_rx.await.unwrap();
+
+    assert_eq!(fetcher.lookup(&url, || {}).as_deref(), Some(&PNG.to_vec()));
+    assert_eq!(server.hits.load(Ordering::SeqCst), 1);
+    assert_eq!(fetcher.lookup("file:///tmp/cover.png", || {}), None);
+}
+
+#[tokio::test]
+async fn failed_lookups_are_not_retried() {
+    let server = stand_in().await;
+    let fetcher = ArtFetcher::new(None, limits());
+    let url = format!("{}/page.html", server.base);
+
+    let (ready_tx, ready_rx) = oneshot::channel();
+    fetcher.lookup(&url, move || ready_tx.send(()).unwrap());
+    ready_rx.await.unwrap();
+
+    assert_eq!(fetcher.lookup(&url, || panic!("retried")), None);
+}
+
+#[tokio::test]
+async fn disk_cache_survives_restarts() {
+    let server = stand_in().await;
+    let dir = tempfile::tempdir().unwrap();
+    let url = format!("{}/cover.png", server.base);
+
+    let fetcher = ArtFetcher::new(Some(dir.path().to_path_buf()), limits());
+    let (ready_tx, ready_rx) = oneshot::channel();
+    fetcher.lookup(&url, move || ready_tx.send(()).unwrap());
+    ready_rx.await.unwrap();
+
+    let restarted = ArtFetcher::new(Some(dir.path().to_path_buf()), limits());
+    let cached = restarted.lookup(&url, || panic!("should come from disk"));
+    assert_eq!(cached.as_deref(), Some(&PNG.to_vec()));
+    assert_eq!(server.hits.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn prune_cache_keeps_the_newest_files() {
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["a", "b", "c"] {
+        std::fs::write(dir.path().join(name), name).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+    }
+
+    prune_cache(dir.path(), 2);
+
+    let mut left: Vec<_> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    left.sort();
+    assert_eq!(left, vec!["b", "c"]);
+}
@@ -0,0 +1,62 @@
+use super::palette;
+use crate::models::ArtPalette;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageFormat};
+use std::io::Cursor;
+
+const JPEG_QUALITY: u8 = 85;
+
+pub struct Variant {
+    pub bytes: Vec<u8>,
+    pub content_type: &'static str,
+}
+
+pub struct Normalized {
+    // one per requested size
+    pub variants: Vec<Variant>,
+    pub palette: Option<ArtPalette>,
+}
+
+// decodes `source` once and re-encodes it to fit each of `sizes` (longest side, px),
+// None if it isn't an image we can decode
+pub fn normalize(source: &[u8], sizes: &[u32]) -> Option<Normalized> {
+    let image = image::load_from_memory(source).ok()?;
+    let variants = sizes
+        .iter()
+        .map(|&px| encode(&fit(&image, px)))
+        .collect::<Option<_>>()?;
+    Some(Normalized {
+        variants,
+        palette: palette::extract(&image),
+    })
+}
+
+// never upscales, a small cover stays small
+fn fit(image: &DynamicImage, px: u32) -> DynamicImage {
+    if image.width() <= px && image.height() <= px {
+        return image.clone();
+    }
+    image.resize(px, px, FilterType::CatmullRom)
+}
+
+// jpeg unless there's transparency to keep
+fn encode(image: &DynamicImage) -> Option<Variant> {
+    let mut bytes = Vec::new();
+    if image.color().has_alpha() {
+        image
+            .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+            .ok()?;
+        return Some(Variant {
+            bytes,
+            content_type: ImageFormat::Png.to_mime_type(),
+        });
+    }
+    JpegEncoder::new_with_quality(&mut bytes, JPEG_QUALITY)
+        .encode_image(&image.to_rgb8())
+        .ok()?;
+    Some(Variant {
+        bytes,
+        content_type: ImageFormat::Jpeg.to_mime_type(),
+    })
+}
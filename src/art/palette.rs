@@ -0,0 +1,128 @@
+use crate::models::ArtPalette;
+use image::DynamicImage;
+use std::cmp::Reverse;
+
+// plenty for picking colors, and fast
+const SAMPLE_PX: u32 = 64;
+// colors covering less of the cover than this are noise
+const MIN_SHARE: f32 = 0.02;
+// one of them always has at least 4.5:1 contrast (wcag aa) with any color
+const WHITE: [f32; 3] = [1.0, 1.0, 1.0];
+const BLACK: [f32; 3] = [0.0, 0.0, 0.0];
+
+#[derive(Clone, Copy, Default)]
+struct Bucket {
+    count: u32,
+    sum: [f32; 3],
+}
+
+impl Bucket {
+    fn color(&self) -> [f32; 3] {
+        self.sum.map(|c| c / self.count as f32)
+    }
+}
+
+// dominant is the most common color, vibrant the most saturated and muted the least
+// saturated of the common ones, text is whatever reads best on dominant
+pub fn extract(image: &DynamicImage) -> Option<ArtPalette> {
+    let sample = image.thumbnail(SAMPLE_PX, SAMPLE_PX).to_rgba8();
+
+    // 4 bits per channel
+    let mut buckets = vec![Bucket::default(); 16 * 16 * 16];
+    let mut total = 0;
+    for pixel in sample.pixels() {
+        let [r, g, b, a] = pixel.0;
+        if a < 128 {
+            continue;
+        }
+        let index = (r as usize >> 4) << 8 | (g as usize >> 4) << 4 | b as usize >> 4;
+        let bucket = &mut buckets[index];
+        bucket.count += 1;
+        for (sum, c) in bucket.sum.iter_mut().zip([r, g, b]) {
+            *sum += c as f32 / 255.0;
+        }
+        total += 1;
+    }
+    if total == 0 {
+        return None;
+    }
+
+    let mut buckets: Vec<Bucket> = buckets.into_iter().filter(|b| b.count > 0).collect();
+    buckets.sort_by_key(|b| Reverse(b.count));
+    // the most common color counts even on a very noisy cover
+    let min_count = total as f32 * MIN_SHARE;
+    let common: Vec<[f32; 3]> = buckets
+        .iter()
+        .enumerate()
+        .filter(|(i, b)| *i == 0 || b.count as f32 >= min_count)
+        .map(|(_, b)| b.color())
+        .collect();
+
+    let dominant = common[0];
+    // too dark or too bright and it doesn't read as a color
+    let usable = |color: &[f32; 3]| (0.15..=0.85).contains(&lightness(*color));
+    let vibrant = common
+        .iter()
+        .copied()
+        .filter(usable)
+        .max_by(|a, b| saturation(*a).total_cmp(&saturation(*b)))
+        .unwrap_or(dominant);
+    let muted = common
+        .iter()
+        .copied()
+        .filter(usable)
+        .min_by(|a, b| saturation(*a).total_cmp(&saturation(*b)))
+        .unwrap_or(dominant);
+    let text = if contrast(WHITE, dominant) >= contrast(BLACK, dominant) {
+        WHITE
+    } else {
+        BLACK
+    };
+
+    Some(ArtPalette {
+        dominant: hex(dominant),
+        vibrant: hex(vibrant),
+        muted: hex(muted),
+        text: hex(text),
+    })
+}
+
+fn lightness([r, g, b]: [f32; 3]) -> f32 {
+    (r.max(g).max(b) + r.min(g).min(b)) / 2.0
+}
+
+// hsl saturation
+fn saturation(color: [f32; 3]) -> f32 {
+    let [r, g, b] = color;
+    let (max, min) = (r.max(g).max(b), r.min(g).min(b));
+    if max == min {
+        return 0.0;
+    }
+    let l = lightness(color);
+    (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+}
+
+// wcag relative luminance
+fn luminance(color: [f32; 3]) -> f32 {
+    let [r, g, b] = color.map(|c| {
+        if c <= 0.03928 {
+            c / 12.92
+        } else {
+            ((c + 0.055) / 1.055).powf(2.4)
+        }
+    });
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+fn contrast(a: [f32; 3], b: [f32; 3]) -> f32 {
+    let (la, lb) = (luminance(a), luminance(b));
+    (la.max(lb) + 0.05) / (la.min(lb) + 0.05)
+}
+
+fn hex(color: [f32; 3]) -> String {
+    let [r, g, b] = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+    format!("#{r:02x}{g:02x}{b:02x}")
+}
+
+#[cfg(test)]
+mod tests;
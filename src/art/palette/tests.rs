@@ -0,0 +1,76 @@
+use super::{contrast, extract};
+use image::{DynamicImage, Rgb, RgbImage, Rgba, RgbaImage};
+
+fn rgb(hex: &str) -> [f32; 3] {
+    let value = u32::from_str_radix(hex.trim_start_matches('#'), 16).unwrap();
+    [16, 8, 0].map(|shift| ((value >> shift) & 0xff) as f32 / 255.0)
+}
+
+// left `share` of the width in `a`, the rest in `b`
+fn split(a: [u8; 3], b: [u8; 3], share: f32) -> DynamicImage {
+    let split_at = (200.0 * share) as u32;
+    DynamicImage::ImageRgb8(RgbImage::from_fn(200, 200, |x, _| {
+        Rgb(if x < split_at { a } else { b })
+    }))
+}
+
+#[test]
+fn solid_cover_is_its_own_palette() {
+    let palette = extract(&split([200, 30, 30], [200, 30, 30], 1.0)).unwrap();
+
+    assert_eq!(palette.dominant, "#c81e1e");
+    assert_eq!(palette.vibrant, "#c81e1e");
+    assert_eq!(palette.muted, "#c81e1e");
+    assert_eq!(palette.text, "#ffffff");
+}
+
+#[test]
+fn vibrant_and_muted_come_from_the_common_colors() {
+    // mostly grey with a red stripe
+    let palette = extract(&split([220, 20, 40], [120, 120, 125], 0.3)).unwrap();
+
+    assert_eq!(palette.dominant, "#78787d");
+    assert_eq!(palette.vibrant, "#dc1428");
+    assert_eq!(palette.muted, "#78787d");
+}
+
+#[test]
+fn rare_colors_are_ignored() {
+    // a few bright pixels on a dark blue cover
+    let mut image = RgbImage::from_pixel(200, 200, Rgb([20, 30, 90]));
+    image.put_pixel(0, 0, Rgb([255, 0, 255]));
+    let palette = extract(&DynamicImage::ImageRgb8(image)).unwrap();
+
+    assert_eq!(palette.vibrant, "#141e5a");
+}
+
+#[test]
+fn text_color_is_readable_on_dominant() {
+    for color in [
+        [0, 0, 0],
+        [255, 255, 255],
+        [118, 118, 118],
+        [255, 200, 0],
+        [0, 0, 160],
+    ] {
+        let palette = extract(&split(color, color, 1.0)).unwrap();
+        let ratio = contrast(rgb(&palette.text), rgb(&palette.dominant));
+        assert!(ratio >= 4.5, "{palette:?} only has {ratio:.2}:1");
+    }
+}
+
+#[test]
+fn transparent_pixels_are_skipped() {
+    let image = RgbaImage::from_fn(100, 100, |x, _| {
+        if x < 50 {
+            Rgba([0, 200, 0, 255])
+        } else {
+            Rgba([255, 0, 0, 0])
+        }
+    });
+    let palette = extract(&DynamicImage::ImageRgba8(image)).unwrap();
+    assert_eq!(palette.dominant, "#00c800");
+
+    let invisible = DynamicImage::ImageRgba8(RgbaImage::new(10, 10));
+    assert_eq!(extract(&invisible), None);
+}
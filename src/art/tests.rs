@@ -0,0 +1,157 @@
+use super::{ArtSizes, ArtStore};
+use crate::models::{OverlayConfig, SongInfo};
+use image::{DynamicImage, ImageFormat, RgbImage, RgbaImage};
+use std::io::Cursor;
+use std::sync::Arc;
+
+const PNG_HEADER: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+
+const SIZES: ArtSizes = ArtSizes {
+    thumbnail_px: 300,
+    background_px: 64,
+};
+
+fn art(bytes: &[u8]) -> Arc<Vec<u8>> {
+    Arc::new(bytes.to_vec())
+}
+
+fn encoded(image: DynamicImage, format: ImageFormat) -> Arc<Vec<u8>> {
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut Cursor::new(&mut bytes), format)
+        .unwrap();
+    Arc::new(bytes)
+}
+
+fn dimensions(bytes: &[u8]) -> (u32, u32) {
+    let image = image::load_from_memory(bytes).unwrap();
+    (image.width(), image.height())
+}
+
+#[test]
+fn large_covers_are_scaled_down_and_reencoded() {
+    let store = ArtStore::default();
+    let cover = encoded(
+        DynamicImage::ImageRgb8(RgbImage::new(1200, 600)),
+        ImageFormat::Png,
+    );
+
+    let published = store.publish(cover, SIZES);
+
+    assert_eq!(published.thumbnail.content_type, "image/jpeg");
+    assert_eq!(dimensions(&published.thumbnail.bytes), (300, 150));
+    assert_eq!(dimensions(&published.background.bytes), (64, 32));
+    assert_ne!(published.thumbnail.hash, published.background.hash);
+    assert_eq!(published.palette.as_ref().unwrap().dominant, "#000000");
+}
+
+#[test]
+fn small_covers_are_not_upscaled() {
+    let store = ArtStore::default();
+    let cover = encoded(
+        DynamicImage::ImageRgb8(RgbImage::new(100, 100)),
+        ImageFormat::Jpeg,
+    );
+
+    let published = store.publish(cover, SIZES);
+
+    assert_eq!(dimensions(&published.thumbnail.bytes), (100, 100));
+    assert_eq!(dimensions(&published.background.bytes), (64, 64));
+}
+
+#[test]
+fn transparency_is_kept_as_png() {
+    let store = ArtStore::default();
+    let cover = encoded(
+        DynamicImage::ImageRgba8(RgbaImage::new(400, 400)),
+        ImageFormat::Png,
+    );
+
+    assert_eq!(
+        store.publish(cover, SIZES).thumbnail.content_type,
+        "image/png"
+    );
+}
+
+#[test]
+fn undecodable_art_is_passed_through() {
+    let store = ArtStore::default();
+
+    let published = store.publish(art(PNG_HEADER), SIZES);
+    assert_eq!(published.thumbnail.content_type, "image/png");
+    assert_eq!(published.thumbnail.bytes.as_slice(), PNG_HEADER);
+    assert_eq!(published.background.hash, published.thumbnail.hash);
+    assert_eq!(published.palette, None);
+
+    let published = store.publish(art(b"not an image"), SIZES);
+    assert_eq!(published.thumbnail.content_type, "application/octet-stream");
+}
+
+#[test]
+fn same_cover_is_only_published_once() {
+    let store = ArtStore::default();
+
+    let first = store.publish(art(PNG_HEADER), SIZES);
+    let second = store.publish(art(PNG_HEADER), SIZES);
+    let other = store.publish(art(b"something else"), SIZES);
+
+    assert!(Arc::ptr_eq(&first, &second));
+    assert_ne!(first.thumbnail.hash, other.thumbnail.hash);
+    assert_eq!(first.thumbnail.hash.len(), 64);
+    assert_eq!(
+        first.thumbnail.url(),
+        format!("/api/art/{}", first.thumbnail.hash)
+    );
+    assert!(store.get(&first.thumbnail.hash).is_some());
+}
+
+#[test]
+fn oldest_entries_are_evicted() {
+    let store = ArtStore::default();
+    let first = store.publish(art(b"0"), SIZES);
+    let kept = store.publish(art(b"1"), SIZES);
+
+    for i in 2..=super::MAX_ENTRIES {
+        store.publish(art(i.to_string().as_bytes()), SIZES);
+        // still in use, moves to the back
+        store.publish(art(b"1"), SIZES);
+    }
+
+    assert!(store.get(&first.thumbnail.hash).is_none());
+    assert!(store.get(&kept.thumbnail.hash).is_some());
+}
+
+#[test]
+fn attach_sets_urls_and_base64_only_on_request() {
+    let store = ArtStore::default();
+    let mut song = SongInfo {
+        album_art: Some(art(b"png")),
+        ..SongInfo::default()
+    };
+    let mut config = OverlayConfig::default();
+
+    store.attach(&mut song, &config);
+    let url = song.album_art_url.clone().unwrap();
+    assert!(url.starts_with("/api/art/"));
+    assert!(song.album_art_background_url.is_some());
+    assert_eq!(
+        song.album_art_mime.as_deref(),
+        Some("application/octet-stream")
+    );
+    assert_eq!(song.album_art_base64, None);
+
+    config.inline_album_art = true;
+    store.attach(&mut song, &config);
+    assert_eq!(song.album_art_url.as_deref(), Some(url.as_str()));
+    assert_eq!(
+        song.album_art_base64.as_deref().map(String::as_str),
+        Some("cG5n")
+    );
+
+    song.album_art = None;
+    store.attach(&mut song, &config);
+    assert_eq!(song.album_art_url, None);
+    assert_eq!(song.album_art_background_url, None);
+    assert_eq!(song.album_art_mime, None);
+    assert_eq!(song.album_art_base64, None);
+}
@@ -0,0 +1,102 @@
+use crate::art::content_hash;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::LazyLock;
+
+// compiled in, so the binary works from any working directory
+const EMBEDDED: &[(&str, &[u8])] = &[
+    ("overlay.html", include_bytes!("../static/overlay.html")),
+    ("overlay.css", include_bytes!("../static/overlay.css")),
+    ("overlay.js", include_bytes!("../static/overlay.js")),
+    ("customize.html", include_bytes!("../static/customize.html")),
+    ("customize.css", include_bytes!("../static/customize.css")),
+    ("customize.js", include_bytes!("../static/customize.js")),
+];
+
+static EMBEDDED_ASSETS: LazyLock<HashMap<&'static str, Asset>> = LazyLock::new(|| {
+    EMBEDDED
+        .iter()
+        .map(|&(name, bytes)| (name, Asset::new(name, Cow::Borrowed(bytes))))
+        .collect()
+});
+
+#[derive(Debug, Clone)]
+pub struct Asset {
+    pub bytes: Cow<'static, [u8]>,
+    pub content_type: &'static str,
+    // quoted, ready for the header
+    pub etag: String,
+}
+
+impl Asset {
+    fn new(name: &str, bytes: Cow<'static, [u8]>) -> Self {
+        let etag = format!("\"{}\"", &content_hash(&bytes)[..16]);
+        Self {
+            content_type: content_type(name),
+            bytes,
+            etag,
+        }
+    }
+}
+
+// where overlay.html and friends come from
+#[derive(Debug, Clone, Default)]
+pub enum StaticFiles {
+    #[default]
+    Embedded,
+    // --static-dir, read on every request so edits show up on reload,
+    // anything missing there still comes from the embedded copy
+    Dir(PathBuf),
+}
+
+impl StaticFiles {
+    // `path` as in the url, "" or "/" is the overlay
+    pub fn get(&self, path: &str) -> Option<Asset> {
+        let name = match path.trim_start_matches('/') {
+            "" => "overlay.html",
+            "customize" => "customize.html",
+            name => name,
+        };
+        if let StaticFiles::Dir(dir) = self
+            && let Some(file) = safe_join(dir, name)
+            && let Ok(bytes) = std::fs::read(file)
+        {
+            return Some(Asset::new(name, Cow::Owned(bytes)));
+        }
+        EMBEDDED_ASSETS.get(name).cloned()
+    }
+}
+
+// None for anything that would leave `dir`
+fn safe_join(dir: &Path, name: &str) -> Option<PathBuf> {
+    let relative = Path::new(name);
+    relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        .then(|| dir.join(relative))
+}
+
+fn content_type(name: &str) -> &'static str {
+    let extension = name.rsplit_once('.').map_or("", |(_, ext)| ext);
+    match extension.to_ascii_lowercase().as_str() {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" | "mjs" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "ttf" => "font/ttf",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests;
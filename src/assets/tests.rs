@@ -0,0 +1,61 @@
+use super::StaticFiles;
+
+#[test]
+fn embedded_assets_have_types_and_etags() {
+    let files = StaticFiles::Embedded;
+
+    let overlay = files.get("/").unwrap();
+    assert_eq!(overlay.content_type, "text/html; charset=utf-8");
+    assert_eq!(overlay.bytes, files.get("/overlay.html").unwrap().bytes);
+    assert_eq!(
+        files.get("/customize").unwrap().content_type,
+        "text/html; charset=utf-8"
+    );
+    assert_eq!(
+        files.get("/overlay.js").unwrap().content_type,
+        "text/javascript; charset=utf-8"
+    );
+    assert_eq!(
+        files.get("/customize.css").unwrap().content_type,
+        "text/css; charset=utf-8"
+    );
+
+    assert_eq!(overlay.etag, files.get("/").unwrap().etag);
+    assert_ne!(overlay.etag, files.get("/overlay.js").unwrap().etag);
+    assert!(files.get("/missing.js").is_none());
+}
+
+#[test]
+fn static_dir_overrides_and_falls_back() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("overlay.css"), "body { color: red; }").unwrap();
+    std::fs::create_dir(dir.path().join("themes")).unwrap();
+    std::fs::write(dir.path().join("themes/neon.css"), "").unwrap();
+    let files = StaticFiles::Dir(dir.path().to_path_buf());
+
+    let css = files.get("/overlay.css").unwrap();
+    assert_eq!(&*css.bytes, b"body { color: red; }");
+    assert_ne!(
+        css.etag,
+        StaticFiles::Embedded.get("/overlay.css").unwrap().etag
+    );
+    assert!(files.get("/themes/neon.css").is_some());
+    // not overridden
+    assert_eq!(
+        files.get("/overlay.js").unwrap().bytes,
+        StaticFiles::Embedded.get("/overlay.js").unwrap().bytes
+    );
+}
+
+#[test]
+fn static_dir_cannot_be_escaped() {
+    let root = tempfile::tempdir().unwrap();
+    std::fs::write(root.path().join("secret.txt"), "secret").unwrap();
+    std::fs::create_dir(root.path().join("static")).unwrap();
+    let files = StaticFiles::Dir(root.path().join("static"));
+
+    assert!(files.get("/../secret.txt").is_none());
+    assert!(files.get("/static/../../secret.txt").is_none());
+    let absolute = root.path().join("secret.txt");
+    assert!(files.get(absolute.to_str().unwrap()).is_none());
+}
@@ -0,0 +1,108 @@
+use crate::config::write_atomic;
+use axum::http::{HeaderValue, Method, header};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+// next to config.json, generated on first start
+pub const TOKEN_FILE: &str = "token";
+
+// in settings.json under "auth"
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct AuthSettings {
+    // fixed token instead of the generated one
+    pub token: Option<String>,
+    // other sites allowed to call the api from a browser, e.g. "https://dashboard.example"
+    pub allowed_origins: Vec<String>,
+}
+
+// guards the routes that change something (config, player control)
+pub struct Auth {
+    token: String,
+    allowed_origins: Vec<HeaderValue>,
+}
+
+impl Auth {
+    pub fn new(token: String, allowed_origins: &[String]) -> Self {
+        let allowed_origins = allowed_origins
+            .iter()
+            .filter_map(|origin| {
+                HeaderValue::from_str(origin.trim_end_matches('/'))
+                    .inspect_err(|_| eprintln!("ignoring allowed origin {origin:?}"))
+                    .ok()
+            })
+            .collect();
+        Self {
+            token,
+            allowed_origins,
+        }
+    }
+
+    // a token that can't be saved still works until the next restart.
+    // a blank one in settings would let anyone in, so it counts as unset
+    pub fn load(settings: &AuthSettings, token_path: &Path) -> Self {
+        let fixed = settings.token.as_ref().filter(|token| {
+            let blank = token.trim().is_empty();
+            if blank {
+                eprintln!("ignoring the empty token in settings.json");
+            }
+            !blank
+        });
+        let token = match fixed {
+            Some(token) => token.clone(),
+            None => load_or_create_token(token_path).unwrap_or_else(|e| {
+                eprintln!("could not save {}: {e}", token_path.display());
+                generate_token()
+            }),
+        };
+        Self::new(token, &settings.allowed_origins)
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    // constant time, so the token can't be guessed byte by byte
+    pub fn check(&self, candidate: &str) -> bool {
+        let (a, b) = (self.token.as_bytes(), candidate.as_bytes());
+        a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+    }
+
+    // same origin pages (overlay, customize) never need cors, so nothing else gets in by default
+    pub fn cors(&self) -> CorsLayer {
+        CorsLayer::new()
+            .allow_origin(AllowOrigin::list(self.allowed_origins.clone()))
+            .allow_methods([Method::GET, Method::POST])
+            .allow_headers([header::AUTHORIZATION, header::CONTENT_TYPE])
+    }
+}
+
+fn load_or_create_token(path: &Path) -> io::Result<String> {
+    match fs::read_to_string(path) {
+        Ok(token) if !token.trim().is_empty() => return Ok(token.trim().to_string()),
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+
+    let token = generate_token();
+    write_atomic(path, token.as_bytes())?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(token)
+}
+
+fn generate_token() -> String {
+    let mut bytes = [0u8; 32];
+    getrandom::fill(&mut bytes).expect("os random source");
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests;
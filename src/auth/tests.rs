@@ -0,0 +1,61 @@
+use super::{Auth, AuthSettings};
+
+#[test]
+fn token_is_generated_once_and_kept() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("token");
+
+    let first = Auth::load(&AuthSettings::default(), &path);
+    let second = Auth::load(&AuthSettings::default(), &path);
+
+    assert_eq!(first.token().len(), 64);
+    assert_eq!(first.token(), second.token());
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), first.token());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+fn token_from_settings_wins() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings = AuthSettings {
+        token: Some("fixed".to_string()),
+        ..AuthSettings::default()
+    };
+
+    let auth = Auth::load(&settings, &dir.path().join("token"));
+
+    assert_eq!(auth.token(), "fixed");
+    assert!(!dir.path().join("token").exists());
+}
+
+#[test]
+fn blank_token_in_settings_is_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    for blank in ["", "  \n"] {
+        let settings = AuthSettings {
+            token: Some(blank.to_string()),
+            ..AuthSettings::default()
+        };
+
+        let auth = Auth::load(&settings, &dir.path().join("token"));
+
+        assert_eq!(auth.token().len(), 64);
+        assert!(!auth.check(""));
+        assert!(!auth.check(blank));
+    }
+}
+
+#[test]
+fn check_needs_the_exact_token() {
+    let auth = Auth::new("secret".to_string(), &[]);
+
+    assert!(auth.check("secret"));
+    assert!(!auth.check("secreT"));
+    assert!(!auth.check("secret2"));
+    assert!(!auth.check(""));
+}
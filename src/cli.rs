@@ -0,0 +1,95 @@
+use clap::{Args, Parser, Subcommand};
+use std::path::PathBuf;
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Show the currently playing song as a stream overlay",
+    args_conflicts_with_subcommands = true
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    // `currentsong --port 4444` without spelling out `serve`
+    #[command(flatten)]
+    pub serve: ServeArgs,
+
+    /// Path to config.json, settings.json is read from the same directory
+    #[arg(long, global = true, env = "CURRENTSONG_CONFIG")]
+    pub config: Option<PathBuf>,
+}
+
+impl Cli {
+    // serve when no subcommand is given
+    pub fn command(self) -> Command {
+        self.command.unwrap_or(Command::Serve(self.serve))
+    }
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Run the overlay server (the default)
+    Serve(ServeArgs),
+
+    /// Print the current track once and exit, for scripts and status bars
+    Now {
+        /// Template for the line, e.g. "{artist} - {title|truncate:40}"
+        #[arg(long, short, value_name = "TEMPLATE")]
+        format: Option<String>,
+
+        /// Print the whole song as json instead
+        #[arg(long, conflicts_with = "format")]
+        json: bool,
+    },
+
+    /// List the players that are open right now
+    Players {
+        /// Print them as json
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Read or change config.json
+    #[command(subcommand)]
+    Config(ConfigCommand),
+}
+
+#[derive(Debug, Subcommand)]
+pub enum ConfigCommand {
+    /// Print one key, or the whole config without one
+    Get { key: Option<String> },
+
+    /// Change one key, the value is json or else taken as a string
+    Set { key: String, value: String },
+}
+
+#[derive(Debug, Default, Args)]
+pub struct ServeArgs {
+    /// Address to bind the overlay server to
+    #[arg(long, env = "CURRENTSONG_HOST")]
+    pub host: Option<String>,
+
+    /// Port to bind the overlay server to
+    #[arg(long, env = "CURRENTSONG_PORT")]
+    pub port: Option<u16>,
+
+    /// Serve overlay and customize files from this folder instead of the built-in ones
+    #[arg(long, value_name = "DIR", env = "CURRENTSONG_STATIC_DIR")]
+    pub static_dir: Option<PathBuf>,
+
+    /// Run without the tray icon, e.g. as a service or over ssh
+    #[arg(long, visible_alias = "no-tray", env = "CURRENTSONG_HEADLESS")]
+    pub headless: bool,
+
+    /// Play back a timeline file instead of reading the system player
+    #[arg(long, value_name = "FILE")]
+    pub script: Option<PathBuf>,
+
+    /// Play back a built-in demo timeline, for previewing themes without music
+    #[arg(long, conflicts_with = "script")]
+    pub demo: bool,
+}
+
+#[cfg(test)]
+mod tests;
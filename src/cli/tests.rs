@@ -0,0 +1,51 @@
+use super::{Cli, Command, ConfigCommand};
+use clap::Parser;
+
+fn parse(args: &[&str]) -> Command {
+    Cli::try_parse_from([&["currentsong"], args].concat())
+        .unwrap()
+        .command()
+}
+
+#[test]
+fn no_subcommand_serves_with_the_top_level_flags() {
+    let Command::Serve(args) = parse(&["--port", "4444", "--headless"]) else {
+        panic!("expected serve");
+    };
+    assert_eq!(args.port, Some(4444));
+    assert!(args.headless);
+
+    let Command::Serve(args) = parse(&["serve", "--demo"]) else {
+        panic!("expected serve");
+    };
+    assert!(args.demo);
+}
+
+#[test]
+fn subcommands_parse_their_arguments() {
+    assert!(matches!(
+        parse(&["now", "-f", "{title}"]),
+        Command::Now { format: Some(f), json: false } if f == "{title}"
+    ));
+    assert!(matches!(
+        parse(&["players", "--json"]),
+        Command::Players { json: true }
+    ));
+    assert!(matches!(
+        parse(&["config", "set", "theme", "vinyl"]),
+        Command::Config(ConfigCommand::Set { key, value }) if key == "theme" && value == "vinyl"
+    ));
+
+    let cli =
+        Cli::try_parse_from(["currentsong", "config", "get", "--config", "/tmp/x.json"]).unwrap();
+    assert_eq!(
+        cli.config.as_deref(),
+        Some(std::path::Path::new("/tmp/x.json"))
+    );
+}
+
+#[test]
+fn server_flags_dont_mix_with_subcommands() {
+    assert!(Cli::try_parse_from(["currentsong", "--port", "4444", "now"]).is_err());
+    assert!(Cli::try_parse_from(["currentsong", "now", "--json", "-f", "{title}"]).is_err());
+}
@@ -0,0 +1,236 @@
+use crate::auth::{self, Auth};
+use crate::cli::{ConfigCommand, ServeArgs};
+use crate::config::{ConfigManager, validate};
+use crate::instance;
+use crate::media_reader::{MediaReader, PlatformMediaReader, PlayerSelection};
+use crate::models::{OverlayConfig, PlaybackState, PlayerSummary, SongInfo};
+use crate::settings::{self, Settings};
+use crate::template::Template;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::Path;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+// one shot subcommands, everything but `serve`. Ok is what to print.
+// with a server running for the same config they go through its api instead
+
+const REMOTE_TIMEOUT: Duration = Duration::from_secs(3);
+
+pub async fn now(config_path: &Path, format: Option<&str>, json: bool) -> Result<String, String> {
+    let template = match format {
+        Some(format) => Template::parse(format).map_err(|e| format!("bad format: {e}"))?,
+        None => Template::default(),
+    };
+    let song: Option<SongInfo> = match Remote::find(config_path) {
+        Some(remote) => remote.get("/api/now-playing").await?,
+        None => {
+            let reader = local_reader(config_path)?;
+            reader.poll_once().song().cloned()
+        }
+    };
+
+    if json {
+        return serde_json::to_string(&song).map_err(|e| e.to_string());
+    }
+    Ok(template.render(song.as_ref()))
+}
+
+pub async fn players(config_path: &Path, json: bool) -> Result<String, String> {
+    let players: Vec<PlayerSummary> = match Remote::find(config_path) {
+        Some(remote) => remote.get("/api/players").await?.unwrap_or_default(),
+        None => {
+            let reader = local_reader(config_path)?;
+            // list_players only knows what the last poll saw
+            reader.poll();
+            reader.list_players()
+        }
+    };
+
+    if json {
+        return serde_json::to_string(&players).map_err(|e| e.to_string());
+    }
+    Ok(format_players(&players))
+}
+
+pub async fn config(config_path: &Path, command: ConfigCommand) -> Result<String, String> {
+    // the server keeps the config in memory and would save over a changed file
+    if let Some(remote) = Remote::find(config_path) {
+        let current: OverlayConfig = remote
+            .get("/api/config")
+            .await?
+            .ok_or("server sent no config")?;
+        return match command {
+            ConfigCommand::Get { key } => config_get(&current, key.as_deref()),
+            ConfigCommand::Set { key, value } => {
+                let config = config_set(&current, &key, &value)?;
+                remote.post_config(config_path, &config).await?;
+                Ok(String::new())
+            }
+        };
+    }
+
+    let manager = load_config(config_path)?;
+    match command {
+        ConfigCommand::Get { key } => config_get(&manager.get_config(), key.as_deref()),
+        ConfigCommand::Set { key, value } => {
+            let config = config_set(&manager.get_config(), &key, &value)?;
+            manager
+                .update_config(config)
+                .map_err(|e| format!("could not save config: {e}"))?;
+            Ok(String::new())
+        }
+    }
+}
+
+fn local_reader(config_path: &Path) -> Result<PlatformMediaReader, String> {
+    let config = load_config(config_path)?.get_config();
+    let reader = PlatformMediaReader::try_new()?;
+    reader.set_player_selection(PlayerSelection::from_config(&config));
+    Ok(reader)
+}
+
+struct Remote {
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl Remote {
+    fn find(config_path: &Path) -> Option<Self> {
+        let info = instance::running(config_path.parent()?)?;
+        let client = reqwest::Client::builder()
+            .timeout(REMOTE_TIMEOUT)
+            .build()
+            .ok()?;
+        Some(Self {
+            base_url: info.base_url,
+            client,
+        })
+    }
+
+    // None for 204
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, String> {
+        let response = self
+            .client
+            .get(format!("{}{path}", self.base_url))
+            .send()
+            .await
+            .map_err(|e| self.unreachable(e))?;
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(None),
+            status if status.is_success() => {
+                let body = response.bytes().await.map_err(|e| self.unreachable(e))?;
+                serde_json::from_slice(&body)
+                    .map(Some)
+                    .map_err(|e| format!("{}{path}: {e}", self.base_url))
+            }
+            status => Err(format!("{}{path} answered {status}", self.base_url)),
+        }
+    }
+
+    async fn post_config(&self, config_path: &Path, config: &OverlayConfig) -> Result<(), String> {
+        let settings = Settings::load(
+            &ServeArgs::default(),
+            &config_path.with_file_name(settings::SETTINGS_FILE),
+        );
+        let auth = Auth::load(
+            &settings.auth,
+            &config_path.with_file_name(auth::TOKEN_FILE),
+        );
+        let response = self
+            .client
+            .post(format!("{}/api/config", self.base_url))
+            .bearer_auth(auth.token())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::to_vec(config).map_err(|e| e.to_string())?)
+            .send()
+            .await
+            .map_err(|e| self.unreachable(e))?;
+        match response.status() {
+            status if status.is_success() => Ok(()),
+            status => Err(format!(
+                "{}/api/config answered {status}: {}",
+                self.base_url,
+                response.text().await.unwrap_or_default()
+            )),
+        }
+    }
+
+    fn unreachable(&self, e: reqwest::Error) -> String {
+        format!(
+            "could not reach the running server at {}: {e}",
+            self.base_url
+        )
+    }
+}
+
+// a broken config.json would otherwise be read (and saved over) as defaults
+fn load_config(config_path: &Path) -> Result<ConfigManager, String> {
+    let (tx, _rx) = broadcast::channel(1);
+    let manager = ConfigManager::new(config_path.to_path_buf(), tx);
+    match manager.status().error {
+        Some(e) => Err(e),
+        None => Ok(manager),
+    }
+}
+
+// strings come out bare so they can be used in scripts as is
+fn config_get(config: &OverlayConfig, key: Option<&str>) -> Result<String, String> {
+    let value = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    let value = match key {
+        Some(key) => value
+            .get(key)
+            .ok_or_else(|| format!("unknown key \"{key}\""))?,
+        None => return serde_json::to_string_pretty(&value).map_err(|e| e.to_string()),
+    };
+    Ok(match value {
+        Value::String(s) => s.clone(),
+        value => value.to_string(),
+    })
+}
+
+// string keys take `value` as is, others parse it as json first so `true`, `42` and `["a"]`
+// work. `null` clears optional ones
+fn config_set(config: &OverlayConfig, key: &str, value: &str) -> Result<OverlayConfig, String> {
+    let mut object = serde_json::to_value(config).map_err(|e| e.to_string())?;
+    let slot = object
+        .get_mut(key)
+        .ok_or_else(|| format!("unknown key \"{key}\""))?;
+    *slot = match slot {
+        _ if value == "null" => Value::Null,
+        Value::String(_) => Value::String(value.to_string()),
+        _ => serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string())),
+    };
+
+    let config: OverlayConfig =
+        serde_json::from_value(object).map_err(|e| format!("{key}: {e}"))?;
+    validate(&config).map_err(|errors| {
+        errors
+            .iter()
+            .map(|e| format!("{}: {}", e.field, e.message))
+            .collect::<Vec<_>>()
+            .join("\n")
+    })?;
+    Ok(config)
+}
+
+// one per line, the followed one starred
+fn format_players(players: &[PlayerSummary]) -> String {
+    players
+        .iter()
+        .map(|p| {
+            let status = match p.status {
+                PlaybackState::Playing => "playing",
+                PlaybackState::Paused => "paused",
+                PlaybackState::Stopped => "stopped",
+            };
+            let marker = if p.followed { '*' } else { ' ' };
+            format!("{marker} {} ({}) {status}", p.identity, p.bus_name)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,65 @@
+use super::{config_get, config_set, format_players};
+use crate::models::{OverlayConfig, PlaybackState, PlayerSummary};
+
+#[test]
+fn get_prints_strings_bare_and_everything_else_as_json() {
+    let config = OverlayConfig::default();
+
+    assert_eq!(config_get(&config, Some("theme")).unwrap(), "frosted_glass");
+    assert_eq!(config_get(&config, Some("font_size_px")).unwrap(), "14");
+    assert_eq!(config_get(&config, Some("ignored_players")).unwrap(), "[]");
+    assert!(config_get(&config, Some("nope")).is_err());
+    assert!(config_get(&config, None).unwrap().contains("\"theme\""));
+}
+
+#[test]
+fn set_parses_by_the_type_of_the_key() {
+    let config = OverlayConfig::default();
+
+    let config = config_set(&config, "theme", "vinyl").unwrap();
+    let config = config_set(&config, "font_size_px", "20").unwrap();
+    let config = config_set(&config, "ignored_players", r#"["*firefox*"]"#).unwrap();
+    let config = config_set(&config, "preferred_player", "spotify").unwrap();
+    assert_eq!(config.theme, "vinyl");
+    assert_eq!(config.font_size_px, 20);
+    assert_eq!(config.ignored_players, ["*firefox*"]);
+    assert_eq!(config.preferred_player.as_deref(), Some("spotify"));
+
+    let config = config_set(&config, "preferred_player", "null").unwrap();
+    assert_eq!(config.preferred_player, None);
+}
+
+#[test]
+fn set_rejects_unknown_keys_wrong_types_and_invalid_values() {
+    let config = OverlayConfig::default();
+
+    assert!(config_set(&config, "nope", "1").is_err());
+    assert!(config_set(&config, "font_size_px", "big").is_err());
+    let err = config_set(&config, "theme", "nope").unwrap_err();
+    assert!(err.starts_with("theme:"), "{err}");
+    let err = config_set(&config, "font_size_px", "500").unwrap_err();
+    assert!(err.starts_with("font_size_px:"), "{err}");
+}
+
+#[test]
+fn players_are_listed_one_per_line_with_the_followed_one_starred() {
+    let players = [
+        PlayerSummary {
+            identity: "Spotify".to_string(),
+            bus_name: "org.mpris.MediaPlayer2.spotify".to_string(),
+            status: PlaybackState::Playing,
+            followed: true,
+        },
+        PlayerSummary {
+            identity: "mpv".to_string(),
+            bus_name: "org.mpris.MediaPlayer2.mpv".to_string(),
+            status: PlaybackState::Paused,
+            followed: false,
+        },
+    ];
+
+    assert_eq!(
+        format_players(&players),
+        "* Spotify (org.mpris.MediaPlayer2.spotify) playing\n  mpv (org.mpris.MediaPlayer2.mpv) paused"
+    );
+}
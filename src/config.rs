@@ -1,33 +1,212 @@
-use crate::models::OverlayConfig;
+use crate::models::{CONFIG_VERSION, OverlayConfig, ServerMessage};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
 use std::fs;
-use std::path::Path;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
+use tokio::sync::broadcast;
+
+mod validate;
+
+pub use validate::validate;
 
 const CONFIG_FILE: &str = "config.json";
+pub const APP_DIR: &str = "currentsong";
+
+// --config flag, else <platform config dir>/currentsong/config.json
+// (~/.config on linux, %APPDATA% on windows)
+pub fn resolve_config_path(cli_path: Option<&Path>) -> PathBuf {
+    if let Some(path) = cli_path {
+        return path.to_path_buf();
+    }
+
+    let Some(dir) = dirs::config_dir() else {
+        return PathBuf::from(CONFIG_FILE);
+    };
+    let path = dir.join(APP_DIR).join(CONFIG_FILE);
+
+    // older builds kept config.json in the working directory, carry it over once
+    let legacy = Path::new(CONFIG_FILE);
+    if !path.exists() && legacy.exists() {
+        match fs::read(legacy).and_then(|content| write_atomic(&path, &content)) {
+            Ok(_) => eprintln!("moved {} to {}", legacy.display(), path.display()),
+            Err(e) => eprintln!(
+                "could not copy {} to {}: {e}",
+                legacy.display(),
+                path.display()
+            ),
+        }
+    }
+
+    path
+}
+
+// write to a sibling temp file then rename over, so readers never see half a file
+pub fn write_atomic(path: &Path, contents: &[u8]) -> io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(".tmp");
+    let tmp_path = path.with_file_name(tmp_name);
+
+    fs::write(&tmp_path, contents)?;
+    fs::rename(&tmp_path, path).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(serde_json::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "could not read config: {e}"),
+            ConfigError::Parse(e) => write!(f, "invalid config: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ConfigError {
+    fn from(e: serde_json::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+// GET /api/config/status
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigStatus {
+    pub path: PathBuf,
+    // set when the file on disk could not be used and defaults were loaded
+    pub error: Option<String>,
+    pub backup_path: Option<PathBuf>,
+}
+
+type Migration = fn(&mut Map<String, Value>);
+
+// MIGRATIONS[n] upgrades a version n file to version n + 1
+const MIGRATIONS: &[Migration] = &[migrate_v0_to_v1];
+
+// v0 is every config.json written before the version field existed. Its
+// fields are all still valid and newer ones are covered by serde defaults.
+fn migrate_v0_to_v1(_config: &mut Map<String, Value>) {}
+
+// returns true if anything had to be upgraded
+fn migrate(value: &mut Value) -> Result<bool, ConfigError> {
+    let Value::Object(config) = value else {
+        return Err(ConfigError::Parse(serde::de::Error::custom(
+            "expected a JSON object",
+        )));
+    };
+
+    let mut version = config.get("version").and_then(Value::as_u64).unwrap_or(0) as usize;
+    let from_version = version;
+
+    // files from a newer build load as-is, unknown fields are ignored
+    while let Some(step) = MIGRATIONS.get(version) {
+        step(config);
+        version += 1;
+        config.insert("version".to_string(), Value::from(version));
+    }
+
+    Ok(version != from_version)
+}
+
+fn parse_config(content: &str) -> Result<(OverlayConfig, bool), ConfigError> {
+    let mut value: Value = serde_json::from_str(content)?;
+    let migrated = migrate(&mut value)?;
+    Ok((serde_json::from_value(value)?, migrated))
+}
+
+fn load_config(path: &Path) -> Result<Option<OverlayConfig>, ConfigError> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    let content = fs::read_to_string(path)?;
+    let (config, migrated) = parse_config(&content)?;
+
+    if migrated {
+        eprintln!(
+            "upgraded {} to config version {CONFIG_VERSION}",
+            path.display()
+        );
+        let json = serde_json::to_string_pretty(&config)?;
+        if let Err(e) = write_atomic(path, json.as_bytes()) {
+            eprintln!("could not save upgraded config: {e}");
+        }
+    }
+
+    Ok(Some(config))
+}
+
+// keep the broken file around, the next save overwrites config.json
+fn backup_config(path: &Path) -> io::Result<PathBuf> {
+    let mut backup_name = path.file_name().unwrap_or_default().to_os_string();
+    backup_name.push(".bak");
+    let backup_path = path.with_file_name(backup_name);
+
+    fs::copy(path, &backup_path)?;
+    Ok(backup_path)
+}
 
 #[derive(Clone)]
 pub struct ConfigManager {
+    path: PathBuf,
     config: Arc<RwLock<OverlayConfig>>,
+    status: Arc<RwLock<ConfigStatus>>,
+    tx: broadcast::Sender<ServerMessage>,
 }
 
 impl ConfigManager {
-    pub fn new() -> Self {
-        let config = if Path::new(CONFIG_FILE).exists() {
-            match fs::read_to_string(CONFIG_FILE) {
-                Ok(content) => {
-                    serde_json::from_str(&content).unwrap_or_else(|_| OverlayConfig::default())
+    pub fn new(path: PathBuf, tx: broadcast::Sender<ServerMessage>) -> Self {
+        let mut status = ConfigStatus {
+            path: path.clone(),
+            error: None,
+            backup_path: None,
+        };
+
+        let config = match load_config(&path) {
+            Ok(config) => config.unwrap_or_default(),
+            Err(e) => {
+                eprintln!("{}: {e}, using defaults", path.display());
+                if let ConfigError::Parse(_) = e {
+                    match backup_config(&path) {
+                        Ok(backup_path) => status.backup_path = Some(backup_path),
+                        Err(backup_err) => eprintln!("could not back up config: {backup_err}"),
+                    }
                 }
-                Err(_) => OverlayConfig::default(),
+                status.error = Some(e.to_string());
+                OverlayConfig::default()
             }
-        } else {
-            OverlayConfig::default()
         };
 
         Self {
+            path,
             config: Arc::new(RwLock::new(config)),
+            status: Arc::new(RwLock::new(status)),
+            tx,
         }
     }
 
+    pub fn status(&self) -> ConfigStatus {
+        self.status.read().unwrap().clone()
+    }
+
     pub fn get_config(&self) -> OverlayConfig {
         self.config.read().unwrap().clone()
     }
@@ -37,7 +216,14 @@ impl ConfigManager {
         *config_guard = new_config.clone();
 
         let json = serde_json::to_string_pretty(&new_config)?;
-        fs::write(CONFIG_FILE, json)?;
+        write_atomic(&self.path, json.as_bytes())?;
+        self.status.write().unwrap().error = None;
+
+        // push to open overlays
+        let _ = self.tx.send(ServerMessage::Config(new_config));
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,261 @@
+use super::validate::MAX_CUSTOM_CSS_BYTES;
+use super::{ConfigManager, MIGRATIONS, migrate, parse_config, validate, write_atomic};
+use crate::models::{CONFIG_VERSION, OverlayConfig, OverlayPosition, ServerMessage};
+use tokio::sync::broadcast;
+
+#[test]
+fn write_atomic_creates_parent_dirs_and_leaves_no_temp_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("nested").join("config.json");
+
+    write_atomic(&path, b"first").unwrap();
+    write_atomic(&path, b"second").unwrap();
+
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "second");
+    let entries: Vec<_> = std::fs::read_dir(path.parent().unwrap())
+        .unwrap()
+        .map(|e| e.unwrap().file_name())
+        .collect();
+    assert_eq!(entries, vec!["config.json"]);
+}
+
+#[test]
+fn missing_file_loads_defaults() {
+    let dir = tempfile::tempdir().unwrap();
+    let (tx, _rx) = broadcast::channel(4);
+    let manager = ConfigManager::new(dir.path().join("config.json"), tx);
+
+    assert_eq!(manager.get_config().theme, OverlayConfig::default().theme);
+}
+
+#[test]
+fn update_config_persists_and_broadcasts() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    let (tx, mut rx) = broadcast::channel(4);
+    let manager = ConfigManager::new(path.clone(), tx.clone());
+
+    let config = OverlayConfig {
+        theme: "vinyl".to_string(),
+        ..OverlayConfig::default()
+    };
+    manager.update_config(config).unwrap();
+
+    let reloaded = ConfigManager::new(path, tx);
+    assert_eq!(reloaded.get_config().theme, "vinyl");
+    match rx.try_recv().unwrap() {
+        ServerMessage::Config(pushed) => assert_eq!(pushed.theme, "vinyl"),
+        other => panic!("unexpected message: {other:?}"),
+    }
+}
+
+#[test]
+fn broken_file_is_backed_up_and_reported() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    std::fs::write(&path, r#"{ "theme": "vinyl", }"#).unwrap();
+    let (tx, _rx) = broadcast::channel(4);
+
+    let manager = ConfigManager::new(path.clone(), tx);
+    let status = manager.status();
+
+    assert_eq!(manager.get_config().theme, OverlayConfig::default().theme);
+    assert!(status.error.unwrap().starts_with("invalid config"));
+    let backup_path = status.backup_path.unwrap();
+    assert_eq!(backup_path, dir.path().join("config.json.bak"));
+    assert_eq!(
+        std::fs::read_to_string(backup_path).unwrap(),
+        r#"{ "theme": "vinyl", }"#
+    );
+}
+
+#[test]
+fn saving_clears_load_error() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    std::fs::write(&path, "not json").unwrap();
+    let (tx, _rx) = broadcast::channel(4);
+
+    let manager = ConfigManager::new(path, tx);
+    assert!(manager.status().error.is_some());
+
+    manager.update_config(OverlayConfig::default()).unwrap();
+    assert!(manager.status().error.is_none());
+}
+
+// MIGRATIONS
+
+// config.json as written by builds before the version field
+const V0_CONFIG: &str = r##"{
+    "theme": "vinyl",
+    "show_thumbnail": false,
+    "show_artist": true,
+    "show_progress": true,
+    "show_time": false,
+    "monitor_index": 0,
+    "position": "TopLeft",
+    "accent_color": "#d4a057",
+    "background_color": "#1e140f",
+    "text_color": "#f5e6d3",
+    "font_size_px": 16,
+    "custom_css": ""
+}"##;
+
+#[test]
+fn migration_chain_reaches_current_version() {
+    assert_eq!(MIGRATIONS.len(), CONFIG_VERSION as usize);
+}
+
+#[test]
+fn migrate_v0_to_v1_keeps_fields_and_fills_defaults() {
+    let (config, migrated) = parse_config(V0_CONFIG).unwrap();
+
+    assert!(migrated);
+    assert_eq!(config.version, 1);
+    assert_eq!(config.theme, "vinyl");
+    assert!(!config.show_thumbnail);
+    assert!(!config.show_time);
+    assert_eq!(config.position, OverlayPosition::TopLeft);
+    assert_eq!(config.font_size_px, 16);
+    // added after v0 shipped
+    assert_eq!(config.border_radius_px, 14);
+    assert_eq!(config.blur_px, 18);
+    assert_eq!(config.transition_animation, "slide_up");
+}
+
+#[test]
+fn current_version_is_not_migrated() {
+    let mut value = serde_json::to_value(OverlayConfig::default()).unwrap();
+
+    assert!(!migrate(&mut value).unwrap());
+}
+
+#[test]
+fn newer_version_loads_and_ignores_unknown_fields() {
+    let (config, migrated) =
+        parse_config(r#"{ "version": 999, "theme": "minimal", "from_the_future": true }"#).unwrap();
+
+    assert!(!migrated);
+    assert_eq!(config.version, 999);
+    assert_eq!(config.theme, "minimal");
+}
+
+#[test]
+fn non_object_config_is_a_parse_error() {
+    assert!(parse_config("[1, 2, 3]").is_err());
+}
+
+#[test]
+fn migrated_file_is_rewritten_at_current_version() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("config.json");
+    std::fs::write(&path, V0_CONFIG).unwrap();
+    let (tx, _rx) = broadcast::channel(4);
+
+    let manager = ConfigManager::new(path.clone(), tx);
+    assert_eq!(manager.get_config().theme, "vinyl");
+
+    let on_disk: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+    assert_eq!(on_disk["version"], CONFIG_VERSION);
+    assert_eq!(on_disk["theme"], "vinyl");
+}
+
+// VALIDATION
+
+fn invalid_fields(config: &OverlayConfig) -> Vec<&'static str> {
+    validate(config)
+        .unwrap_err()
+        .into_iter()
+        .map(|e| e.field)
+        .collect()
+}
+
+#[test]
+fn default_config_is_valid() {
+    assert!(validate(&OverlayConfig::default()).is_ok());
+}
+
+#[test]
+fn unknown_theme_and_animation_are_rejected() {
+    let config = OverlayConfig {
+        theme: "comic_sans".to_string(),
+        transition_animation: "explode".to_string(),
+        ..OverlayConfig::default()
+    };
+
+    assert_eq!(
+        invalid_fields(&config),
+        vec!["theme", "transition_animation"]
+    );
+}
+
+#[test]
+fn colors_must_be_hex() {
+    let config = OverlayConfig {
+        accent_color: "red".to_string(),
+        background_color: "#12345".to_string(),
+        text_color: "#fff".to_string(),
+        ..OverlayConfig::default()
+    };
+
+    assert_eq!(
+        invalid_fields(&config),
+        vec!["accent_color", "background_color"]
+    );
+}
+
+#[test]
+fn sizes_must_be_in_range() {
+    let config = OverlayConfig {
+        font_size_px: 0,
+        border_radius_px: 1000,
+        blur_px: 101,
+        art_thumbnail_px: 4096,
+        art_background_px: 0,
+        ..OverlayConfig::default()
+    };
+
+    assert_eq!(
+        invalid_fields(&config),
+        vec![
+            "font_size_px",
+            "border_radius_px",
+            "blur_px",
+            "art_thumbnail_px",
+            "art_background_px"
+        ]
+    );
+}
+
+#[test]
+fn file_output_stays_inside_its_dir() {
+    let mut config = OverlayConfig::default();
+    config.file_output.title = Some("../title.txt".into());
+    config.file_output.artist = Some("/etc/artist.txt".into());
+    config.file_output.album = Some("nested/album.txt".into());
+    config.file_output.nowplaying = None;
+    config.file_output.nowplaying_template = "{nope}".to_string();
+
+    assert_eq!(
+        invalid_fields(&config),
+        vec![
+            "file_output.title",
+            "file_output.artist",
+            "file_output.album",
+            "file_output.nowplaying_template"
+        ]
+    );
+}
+
+#[test]
+fn custom_css_is_length_limited() {
+    let mut config = OverlayConfig {
+        custom_css: "a".repeat(MAX_CUSTOM_CSS_BYTES),
+        ..OverlayConfig::default()
+    };
+    assert!(validate(&config).is_ok());
+
+    config.custom_css.push('a');
+    assert_eq!(invalid_fields(&config), vec!["custom_css"]);
+}
@@ -0,0 +1,160 @@
+use crate::file_output::FileOutputSettings;
+use crate::models::OverlayConfig;
+use crate::template::Template;
+use serde::Serialize;
+use std::ops::RangeInclusive;
+use std::path::Component;
+
+// keep in sync with the theme-* / anim-* classes in static/overlay.css
+pub const THEMES: &[&str] = &[
+    "frosted_glass",
+    "neon_glow",
+    "vinyl",
+    "minimal",
+    "spotify",
+    "cyberpunk",
+    "pastel_dream",
+    "gradient_wave",
+];
+pub const TRANSITION_ANIMATIONS: &[&str] =
+    &["none", "fade", "slide_up", "slide_left", "flip", "bounce"];
+
+const FONT_SIZE_PX: RangeInclusive<u32> = 8..=72;
+const BORDER_RADIUS_PX: RangeInclusive<u32> = 0..=64;
+const BLUR_PX: RangeInclusive<u32> = 0..=100;
+const ART_THUMBNAIL_PX: RangeInclusive<u32> = 32..=1024;
+const ART_BACKGROUND_PX: RangeInclusive<u32> = 8..=512;
+pub const MAX_CUSTOM_CSS_BYTES: usize = 64 * 1024;
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct FieldError {
+    pub field: &'static str,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            field,
+            message: message.into(),
+        }
+    }
+}
+
+pub fn validate(config: &OverlayConfig) -> Result<(), Vec<FieldError>> {
+    let mut errors = Vec::new();
+
+    if !THEMES.contains(&config.theme.as_str()) {
+        errors.push(FieldError::new(
+            "theme",
+            format!("unknown theme \"{}\"", config.theme),
+        ));
+    }
+    if !TRANSITION_ANIMATIONS.contains(&config.transition_animation.as_str()) {
+        errors.push(FieldError::new(
+            "transition_animation",
+            format!("unknown animation \"{}\"", config.transition_animation),
+        ));
+    }
+
+    for (field, value) in [
+        ("accent_color", &config.accent_color),
+        ("background_color", &config.background_color),
+        ("text_color", &config.text_color),
+    ] {
+        if !is_hex_color(value) {
+            errors.push(FieldError::new(
+                field,
+                format!("\"{value}\" is not a hex color like #1a2b3c"),
+            ));
+        }
+    }
+
+    for (field, value, range) in [
+        ("font_size_px", config.font_size_px, FONT_SIZE_PX),
+        (
+            "border_radius_px",
+            config.border_radius_px,
+            BORDER_RADIUS_PX,
+        ),
+        ("blur_px", config.blur_px, BLUR_PX),
+        (
+            "art_thumbnail_px",
+            config.art_thumbnail_px,
+            ART_THUMBNAIL_PX,
+        ),
+        (
+            "art_background_px",
+            config.art_background_px,
+            ART_BACKGROUND_PX,
+        ),
+    ] {
+        if !range.contains(&value) {
+            errors.push(FieldError::new(
+                field,
+                format!(
+                    "must be between {} and {}, got {value}",
+                    range.start(),
+                    range.end()
+                ),
+            ));
+        }
+    }
+
+    if config.custom_css.len() > MAX_CUSTOM_CSS_BYTES {
+        errors.push(FieldError::new(
+            "custom_css",
+            format!(
+                "must be at most {} KB, got {} KB",
+                MAX_CUSTOM_CSS_BYTES / 1024,
+                config.custom_css.len().div_ceil(1024)
+            ),
+        ));
+    }
+
+    validate_file_output(&config.file_output, &mut errors);
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+// the api can set these. the dir itself only comes from settings.json,
+// names are kept to plain file names so nothing is written outside it
+fn validate_file_output(settings: &FileOutputSettings, errors: &mut Vec<FieldError>) {
+    for (field, name) in [
+        ("file_output.title", &settings.title),
+        ("file_output.artist", &settings.artist),
+        ("file_output.album", &settings.album),
+        ("file_output.nowplaying", &settings.nowplaying),
+        ("file_output.cover", &settings.cover),
+    ] {
+        if let Some(name) = name
+            && !matches!(
+                name.components().collect::<Vec<_>>()[..],
+                [Component::Normal(_)]
+            )
+        {
+            errors.push(FieldError::new(
+                field,
+                format!("\"{}\" is not a plain file name", name.display()),
+            ));
+        }
+    }
+
+    if let Err(e) = Template::parse(&settings.nowplaying_template) {
+        errors.push(FieldError::new(
+            "file_output.nowplaying_template",
+            e.to_string(),
+        ));
+    }
+}
+
+// #rgb, #rrggbb or #rrggbbaa
+fn is_hex_color(value: &str) -> bool {
+    value.strip_prefix('#').is_some_and(|hex| {
+        matches!(hex.len(), 3 | 6 | 8) && hex.chars().all(|c| c.is_ascii_hexdigit())
+    })
+}
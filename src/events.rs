@@ -0,0 +1,114 @@
+use crate::models::ServerMessage;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast::{self, error::RecvError};
+
+// how far back a reconnecting client can resume from
+const MAX_REMEMBERED: usize = 256;
+
+#[derive(Debug, Clone)]
+pub struct Event {
+    pub id: u64,
+    pub message: ServerMessage,
+}
+
+#[derive(Default)]
+struct Log {
+    // id of the newest event, 0 before the first
+    last_id: u64,
+    events: VecDeque<Arc<Event>>,
+}
+
+// numbers everything sent on the broadcast channel and keeps the latest ones,
+// for clients that resume with an id (sse Last-Event-ID)
+pub struct EventLog {
+    // random per launch and part of every id, so ids from before a restart aren't
+    // mistaken for this run's
+    epoch: u64,
+    log: Arc<Mutex<Log>>,
+    tx: broadcast::Sender<Arc<Event>>,
+}
+
+pub struct Subscription {
+    pub last_id: u64,
+    // everything after the requested id, None if it's unknown or too old to replay
+    pub missed: Option<Vec<Arc<Event>>>,
+    pub rx: broadcast::Receiver<Arc<Event>>,
+}
+
+impl EventLog {
+    // must be called inside the tokio runtime
+    pub fn new(source: &broadcast::Sender<ServerMessage>) -> Self {
+        let (tx, _rx) = broadcast::channel(100);
+        let log = Arc::new(Mutex::new(Log::default()));
+
+        let mut source = source.subscribe();
+        let (task_log, task_tx) = (log.clone(), tx.clone());
+        tokio::spawn(async move {
+            loop {
+                match source.recv().await {
+                    Ok(message) => {
+                        // numbered and sent under the lock so subscribe never misses one
+                        let mut log = task_log.lock().unwrap();
+                        log.last_id += 1;
+                        let event = Arc::new(Event {
+                            id: log.last_id,
+                            message,
+                        });
+                        if log.events.len() == MAX_REMEMBERED {
+                            log.events.pop_front();
+                        }
+                        log.events.push_back(event.clone());
+                        let _ = task_tx.send(event);
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => break,
+                }
+            }
+        });
+
+        let mut epoch = [0; 8];
+        getrandom::fill(&mut epoch).expect("os random source");
+        EventLog {
+            epoch: u64::from_le_bytes(epoch),
+            log,
+            tx,
+        }
+    }
+
+    // what clients see as the event id, "<epoch>-<number>"
+    pub fn id(&self, number: u64) -> String {
+        format!("{:x}-{number}", self.epoch)
+    }
+
+    // the number in an id handed out by this run, None for anything else
+    pub fn parse_id(&self, id: &str) -> Option<u64> {
+        let (epoch, number) = id.trim().split_once('-')?;
+        (u64::from_str_radix(epoch, 16).ok()? == self.epoch)
+            .then(|| number.parse().ok())
+            .flatten()
+    }
+
+    pub fn subscribe(&self, after: Option<u64>) -> Subscription {
+        let log = self.log.lock().unwrap();
+        let missed = after.and_then(|after| {
+            let oldest = log.events.front().map_or(log.last_id + 1, |e| e.id);
+            // after == last_id means nothing was missed
+            (after.saturating_add(1) >= oldest && after <= log.last_id).then(|| {
+                log.events
+                    .iter()
+                    .filter(|e| e.id > after)
+                    .cloned()
+                    .collect()
+            })
+        });
+        Subscription {
+            last_id: log.last_id,
+            missed,
+            rx: self.tx.subscribe(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
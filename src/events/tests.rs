@@ -0,0 +1,75 @@
+use super::{EventLog, MAX_REMEMBERED};
+use crate::models::{PlayerEvent, ServerMessage};
+use tokio::sync::broadcast;
+
+async fn send(tx: &broadcast::Sender<ServerMessage>, log: &EventLog, count: usize) {
+    let mut rx = log.subscribe(None).rx;
+    for _ in 0..count {
+        tx.send(ServerMessage::Song(PlayerEvent::Stopped)).unwrap();
+        rx.recv().await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn events_are_numbered_in_order() {
+    let (tx, _rx) = broadcast::channel(100);
+    let log = EventLog::new(&tx);
+    let mut rx = log.subscribe(None).rx;
+
+    tx.send(ServerMessage::Song(PlayerEvent::Stopped)).unwrap();
+    tx.send(ServerMessage::Players(Vec::new())).unwrap();
+
+    assert_eq!(rx.recv().await.unwrap().id, 1);
+    let second = rx.recv().await.unwrap();
+    assert_eq!(second.id, 2);
+    assert!(matches!(second.message, ServerMessage::Players(_)));
+}
+
+#[tokio::test]
+async fn resuming_replays_only_what_was_missed() {
+    let (tx, _rx) = broadcast::channel(100);
+    let log = EventLog::new(&tx);
+    send(&tx, &log, 5).await;
+
+    let resumed = log.subscribe(Some(3));
+    assert_eq!(resumed.last_id, 5);
+    let ids: Vec<_> = resumed.missed.unwrap().iter().map(|e| e.id).collect();
+    assert_eq!(ids, [4, 5]);
+
+    assert!(log.subscribe(Some(5)).missed.unwrap().is_empty());
+    assert!(log.subscribe(None).missed.is_none());
+    // from before a restart
+    assert!(log.subscribe(Some(99)).missed.is_none());
+}
+
+#[tokio::test]
+async fn ids_older_than_the_buffer_get_no_replay() {
+    let (tx, _rx) = broadcast::channel(100);
+    let log = EventLog::new(&tx);
+    send(&tx, &log, MAX_REMEMBERED + 10).await;
+
+    assert!(log.subscribe(Some(5)).missed.is_none());
+    let missed = log.subscribe(Some(10)).missed.unwrap();
+    assert_eq!(missed.len(), MAX_REMEMBERED);
+}
+
+#[tokio::test]
+async fn huge_ids_get_no_replay() {
+    let (tx, _rx) = broadcast::channel(100);
+    let log = EventLog::new(&tx);
+    send(&tx, &log, 3).await;
+
+    assert!(log.subscribe(Some(u64::MAX)).missed.is_none());
+}
+
+#[tokio::test]
+async fn only_ids_from_this_run_are_understood() {
+    let (tx, _rx) = broadcast::channel(100);
+    let log = EventLog::new(&tx);
+    let previous_run = EventLog::new(&tx);
+
+    assert_eq!(log.parse_id(&log.id(42)), Some(42));
+    assert_eq!(log.parse_id(&previous_run.id(42)), None);
+    assert_eq!(log.parse_id("42"), None);
+    assert_eq!(log.parse_id("nope"), None);
+}
@@ -0,0 +1,194 @@
+use crate::config::write_atomic;
+use crate::models::{PlayerEvent, ServerMessage, SongInfo};
+use crate::template::{DEFAULT_TEMPLATE, Template};
+use image::{ImageFormat, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::path::PathBuf;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+// next to config.json unless settings.json says otherwise
+pub const OUTPUT_DIR: &str = "nowplaying";
+
+// in config.json under "file_output", for obs text and image sources that read files
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct FileOutputSettings {
+    pub enabled: bool,
+    // plain file names inside the output dir, null to skip a file
+    pub title: Option<PathBuf>,
+    pub artist: Option<PathBuf>,
+    pub album: Option<PathBuf>,
+    pub nowplaying: Option<PathBuf>,
+    pub cover: Option<PathBuf>,
+    // what goes into nowplaying, see template.rs
+    pub nowplaying_template: String,
+}
+
+impl Default for FileOutputSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            title: Some("title.txt".into()),
+            artist: Some("artist.txt".into()),
+            album: Some("album.txt".into()),
+            nowplaying: Some("nowplaying.txt".into()),
+            cover: Some("cover.png".into()),
+            nowplaying_template: DEFAULT_TEMPLATE.to_string(),
+        }
+    }
+}
+
+// what went into each file last, so position ticks don't touch the disk
+#[derive(PartialEq)]
+enum Written {
+    Text(String),
+    // album_art_url, it changes exactly when the image does
+    Cover(Option<String>),
+}
+
+pub struct FileOutput {
+    settings: FileOutputSettings,
+    dir: PathBuf,
+    template: Template,
+    written: HashMap<PathBuf, Written>,
+}
+
+impl FileOutput {
+    pub fn new(settings: FileOutputSettings, dir: PathBuf) -> Self {
+        let template = Template::parse(&settings.nowplaying_template).unwrap_or_else(|e| {
+            eprintln!("ignoring file_output.nowplaying_template: {e}");
+            Template::default()
+        });
+        Self {
+            settings,
+            dir,
+            template,
+            written: HashMap::new(),
+        }
+    }
+
+    // stopped or gone empties the text files and blanks the cover
+    pub fn update(&mut self, event: &PlayerEvent) {
+        let song = event.song();
+        let text =
+            |field: fn(&SongInfo) -> String| Written::Text(song.map(field).unwrap_or_default());
+
+        let files = [
+            (&self.settings.title, text(|s| s.title.clone())),
+            (&self.settings.artist, text(|s| s.artist.clone())),
+            (&self.settings.album, text(|s| s.album.clone())),
+            (
+                &self.settings.nowplaying,
+                Written::Text(self.template.render(song)),
+            ),
+            (
+                &self.settings.cover,
+                Written::Cover(song.and_then(|s| s.album_art_url.clone())),
+            ),
+        ];
+        for (name, content) in files {
+            let Some(name) = name else { continue };
+            let path = self.dir.join(name);
+            if self.written.get(&path) == Some(&content) {
+                continue;
+            }
+
+            let bytes = match &content {
+                Written::Text(text) => text.clone().into_bytes(),
+                Written::Cover(_) => cover_png(song.and_then(|s| s.album_art.as_deref())),
+            };
+            match write_atomic(&path, &bytes) {
+                Ok(()) => {
+                    self.written.insert(path, content);
+                }
+                Err(e) => eprintln!("could not write {}: {e}", path.display()),
+            }
+        }
+    }
+}
+
+// a transparent pixel when there's no art, a missing file makes obs keep the old image
+fn cover_png(art: Option<&Vec<u8>>) -> Vec<u8> {
+    if let Some(art) = art {
+        if image::guess_format(art).ok() == Some(ImageFormat::Png) {
+            return art.clone();
+        }
+        let mut bytes = Vec::new();
+        if let Ok(image) = image::load_from_memory(art)
+            && image
+                .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+                .is_ok()
+        {
+            return bytes;
+        }
+    }
+
+    let mut bytes = Vec::new();
+    RgbaImage::new(1, 1)
+        .write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .expect("encoding a pixel can't fail");
+    bytes
+}
+
+// keeps the files in step with the broadcast, rebuilding the output when the config changes
+pub struct FileOutputFollower {
+    dir: PathBuf,
+    settings: FileOutputSettings,
+    output: Option<FileOutput>,
+    // written right away when output gets turned on or moved
+    last_event: PlayerEvent,
+}
+
+impl FileOutputFollower {
+    pub fn new(settings: FileOutputSettings, dir: PathBuf) -> Self {
+        let output = settings
+            .enabled
+            .then(|| FileOutput::new(settings.clone(), dir.clone()));
+        Self {
+            dir,
+            settings,
+            output,
+            last_event: PlayerEvent::Stopped,
+        }
+    }
+
+    pub fn handle(&mut self, message: &ServerMessage) {
+        match message {
+            ServerMessage::Song(event) => {
+                if let Some(output) = &mut self.output {
+                    output.update(event);
+                }
+                self.last_event = event.clone();
+            }
+            ServerMessage::Config(config) if config.file_output != self.settings => {
+                self.settings = config.file_output.clone();
+                self.output = self
+                    .settings
+                    .enabled
+                    .then(|| FileOutput::new(self.settings.clone(), self.dir.clone()));
+                if let Some(output) = &mut self.output {
+                    output.update(&self.last_event);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// follows the same broadcast as /ws, on its own thread since it does blocking io
+pub fn spawn(mut follower: FileOutputFollower, mut rx: broadcast::Receiver<ServerMessage>) {
+    std::thread::spawn(move || {
+        loop {
+            match rx.blocking_recv() {
+                Ok(message) => follower.handle(&message),
+                Err(RecvError::Lagged(_)) => {}
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests;
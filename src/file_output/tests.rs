@@ -0,0 +1,153 @@
+use super::{FileOutput, FileOutputFollower, FileOutputSettings};
+use crate::models::{OverlayConfig, PlayerEvent, ServerMessage, SongInfo};
+use image::{DynamicImage, ImageFormat, RgbImage};
+use std::io::Cursor;
+use std::path::Path;
+use std::sync::Arc;
+
+fn song(title: &str, artist: &str) -> SongInfo {
+    SongInfo {
+        title: title.to_string(),
+        artist: artist.to_string(),
+        album: "Album".to_string(),
+        is_playing: true,
+        ..SongInfo::default()
+    }
+}
+
+fn read(dir: &Path, name: &str) -> String {
+    std::fs::read_to_string(dir.join(name)).unwrap()
+}
+
+fn enabled() -> FileOutputSettings {
+    FileOutputSettings {
+        enabled: true,
+        ..FileOutputSettings::default()
+    }
+}
+
+#[test]
+fn writes_text_files_and_clears_them_on_stop() {
+    let dir = tempfile::tempdir().unwrap();
+    let out = dir.path().join("out");
+    let mut output = FileOutput::new(enabled(), out.clone());
+
+    output.update(&PlayerEvent::TrackChanged(song("Title", "Artist")));
+    assert_eq!(read(&out, "title.txt"), "Title");
+    assert_eq!(read(&out, "artist.txt"), "Artist");
+    assert_eq!(read(&out, "album.txt"), "Album");
+    assert_eq!(read(&out, "nowplaying.txt"), "Artist - Title");
+
+    output.update(&PlayerEvent::TrackChanged(song("Solo", "")));
+    assert_eq!(read(&out, "nowplaying.txt"), "Solo");
+
+    output.update(&PlayerEvent::Stopped);
+    assert_eq!(read(&out, "title.txt"), "");
+    assert_eq!(read(&out, "nowplaying.txt"), "");
+}
+
+#[test]
+fn position_ticks_do_not_rewrite_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut output = FileOutput::new(enabled(), dir.path().to_path_buf());
+    let mut playing = song("Title", "Artist");
+
+    output.update(&PlayerEvent::TrackChanged(playing.clone()));
+    std::fs::remove_file(dir.path().join("title.txt")).unwrap();
+    playing.position_secs = 10;
+    output.update(&PlayerEvent::TrackChanged(playing));
+
+    assert!(!dir.path().join("title.txt").exists());
+}
+
+#[test]
+fn cover_is_always_a_png() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut output = FileOutput::new(enabled(), dir.path().to_path_buf());
+    let cover = dir.path().join("cover.png");
+
+    output.update(&PlayerEvent::Stopped);
+    let blank = image::load_from_memory(&std::fs::read(&cover).unwrap()).unwrap();
+    assert_eq!((blank.width(), blank.height()), (1, 1));
+
+    let mut jpeg = Vec::new();
+    DynamicImage::ImageRgb8(RgbImage::new(20, 10))
+        .write_to(&mut Cursor::new(&mut jpeg), ImageFormat::Jpeg)
+        .unwrap();
+    let with_art = SongInfo {
+        album_art: Some(Arc::new(jpeg)),
+        album_art_url: Some("/api/art/abc".to_string()),
+        ..song("Title", "Artist")
+    };
+    output.update(&PlayerEvent::TrackChanged(with_art));
+
+    let bytes = std::fs::read(&cover).unwrap();
+    assert_eq!(image::guess_format(&bytes).unwrap(), ImageFormat::Png);
+    let written = image::load_from_memory(&bytes).unwrap();
+    assert_eq!((written.width(), written.height()), (20, 10));
+}
+
+#[test]
+fn files_set_to_null_are_skipped() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings: FileOutputSettings =
+        serde_json::from_str(r#"{"enabled": true, "album": null, "cover": null}"#).unwrap();
+    let mut output = FileOutput::new(settings, dir.path().to_path_buf());
+
+    output.update(&PlayerEvent::TrackChanged(song("Title", "Artist")));
+
+    assert_eq!(read(dir.path(), "title.txt"), "Title");
+    assert!(!dir.path().join("album.txt").exists());
+    assert!(!dir.path().join("cover.png").exists());
+}
+
+#[test]
+fn nowplaying_uses_the_template() {
+    let dir = tempfile::tempdir().unwrap();
+    let settings = FileOutputSettings {
+        nowplaying_template: "♪ {title|upper} ({length})".to_string(),
+        ..enabled()
+    };
+    let mut output = FileOutput::new(settings, dir.path().to_path_buf());
+    let playing = SongInfo {
+        length_secs: 95,
+        ..song("Title", "Artist")
+    };
+
+    output.update(&PlayerEvent::TrackChanged(playing));
+
+    assert_eq!(read(dir.path(), "nowplaying.txt"), "♪ TITLE (1:35)");
+}
+
+#[test]
+fn turning_output_on_in_the_config_writes_the_current_song() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut follower =
+        FileOutputFollower::new(FileOutputSettings::default(), dir.path().to_path_buf());
+    let config = |file_output| {
+        ServerMessage::Config(OverlayConfig {
+            file_output,
+            ..OverlayConfig::default()
+        })
+    };
+
+    follower.handle(&ServerMessage::Song(PlayerEvent::TrackChanged(song(
+        "Title", "Artist",
+    ))));
+    assert!(!dir.path().join("title.txt").exists());
+
+    follower.handle(&config(enabled()));
+    assert_eq!(read(dir.path(), "title.txt"), "Title");
+
+    // renamed files are picked up too
+    let renamed = FileOutputSettings {
+        title: Some("song.txt".into()),
+        ..enabled()
+    };
+    follower.handle(&config(renamed));
+    assert_eq!(read(dir.path(), "song.txt"), "Title");
+
+    follower.handle(&config(FileOutputSettings::default()));
+    follower.handle(&ServerMessage::Song(PlayerEvent::Stopped));
+    assert_eq!(read(dir.path(), "song.txt"), "Title");
+}
@@ -0,0 +1,270 @@
+use crate::config::write_atomic;
+use crate::models::{PlayerEvent, PlayerSummary, SongInfo};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+// shorter than this and it was a skip, not a play
+const MIN_PLAYED: Duration = Duration::from_secs(1);
+// back this close to the start after most of the track played is a new play of it
+const RESTART_SECS: u64 = 10;
+
+// in settings.json under "history"
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(default)]
+pub struct HistorySettings {
+    pub enabled: bool,
+    // 0 keeps everything
+    pub max_age_days: u64,
+    pub max_entries: usize,
+}
+
+impl Default for HistorySettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_age_days: 30,
+            max_entries: 10_000,
+        }
+    }
+}
+
+// one line of history.jsonl, times are unix seconds
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HistoryEntry {
+    pub started_at: u64,
+    pub ended_at: u64,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub length_secs: u64,
+    // time spent actually playing, pauses don't count
+    pub played_secs: u64,
+    // identity of the player it came from
+    pub player: Option<String>,
+}
+
+struct Entries {
+    // oldest first
+    entries: VecDeque<HistoryEntry>,
+    // lines in the file, can be more than `entries` until the next compaction
+    file_lines: usize,
+}
+
+// finished plays, appended to a jsonl file and kept in memory for queries
+pub struct History {
+    path: PathBuf,
+    settings: HistorySettings,
+    inner: Mutex<Entries>,
+}
+
+impl History {
+    pub fn load(path: PathBuf, settings: HistorySettings) -> Self {
+        let mut entries = VecDeque::new();
+        let mut file_lines = 0;
+        if settings.enabled {
+            match fs::read_to_string(&path) {
+                Ok(content) => {
+                    for line in content.lines().filter(|l| !l.trim().is_empty()) {
+                        file_lines += 1;
+                        // a crash mid write leaves half a line, drop it
+                        if let Ok(entry) = serde_json::from_str(line) {
+                            entries.push_back(entry);
+                        }
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => eprintln!("could not read {}: {e}", path.display()),
+            }
+        }
+
+        let history = History {
+            path,
+            settings,
+            inner: Mutex::new(Entries {
+                entries,
+                file_lines,
+            }),
+        };
+        history.retain(&mut history.inner.lock().unwrap(), unix_now());
+        history
+    }
+
+    pub fn record(&self, entry: HistoryEntry) {
+        if !self.settings.enabled {
+            return;
+        }
+        let mut inner = self.inner.lock().unwrap();
+        let now = entry.ended_at;
+        if let Err(e) = append_line(&self.path, &entry) {
+            eprintln!("could not write {}: {e}", self.path.display());
+        }
+        inner.entries.push_back(entry);
+        inner.file_lines += 1;
+        self.retain(&mut inner, now);
+    }
+
+    // newest first, ended at or after `since`
+    pub fn query(&self, since: Option<u64>, limit: usize) -> Vec<HistoryEntry> {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .entries
+            .iter()
+            .rev()
+            .take_while(|entry| since.is_none_or(|since| entry.ended_at >= since))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+
+    // drops what's past retention, rewriting the file once enough lines are stale
+    fn retain(&self, inner: &mut Entries, now: u64) {
+        let max_age = self.settings.max_age_days * DAY_SECS;
+        while inner.entries.len() > self.settings.max_entries
+            || inner
+                .entries
+                .front()
+                .is_some_and(|e| max_age > 0 && e.ended_at + max_age < now)
+        {
+            inner.entries.pop_front();
+        }
+
+        // appending is cheap, rewriting isn't, so let some stale lines pile up first
+        let slack = (self.settings.max_entries / 10).max(16);
+        if inner.file_lines > inner.entries.len() + slack {
+            let mut contents = Vec::new();
+            for entry in &inner.entries {
+                serde_json::to_writer(&mut contents, entry).expect("entry serializes");
+                contents.push(b'\n');
+            }
+            match write_atomic(&self.path, &contents) {
+                Ok(()) => inner.file_lines = inner.entries.len(),
+                Err(e) => eprintln!("could not compact {}: {e}", self.path.display()),
+            }
+        }
+    }
+}
+
+fn append_line(path: &Path, entry: &HistoryEntry) -> io::Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        fs::create_dir_all(parent)?;
+    }
+    let mut line = serde_json::to_vec(entry)?;
+    line.push(b'\n');
+    // a single write, so lines from a crash are torn at worst, never interleaved
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?
+        .write_all(&line)
+}
+
+pub fn unix_now() -> u64 {
+    unix_secs(SystemTime::now())
+}
+
+fn unix_secs(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+struct Play {
+    entry: HistoryEntry,
+    started: SystemTime,
+    played: Duration,
+    // set while playing, time since then is added to `played` on the next poll
+    playing_since: Option<SystemTime>,
+    // which player, identity can be shared by several (two mpv windows)
+    bus_name: Option<String>,
+    last_position: u64,
+}
+
+// turns the stream of polls into finished plays
+#[derive(Default)]
+pub struct PlayTracker {
+    current: Option<Play>,
+}
+
+impl PlayTracker {
+    // feed every poll, returns the play that just ended if the track changed or stopped
+    pub fn observe(
+        &mut self,
+        event: &PlayerEvent,
+        player: Option<&PlayerSummary>,
+        now: SystemTime,
+    ) -> Option<HistoryEntry> {
+        if let Some(play) = &mut self.current
+            && let Some(since) = play.playing_since
+        {
+            play.played += now.duration_since(since).unwrap_or_default();
+            play.playing_since = Some(now);
+        }
+
+        let song = event.song();
+        let bus_name = player.map(|p| p.bus_name.as_str());
+        if let (Some(play), Some(song)) = (&mut self.current, song)
+            && play.continues(song, bus_name)
+        {
+            play.entry.length_secs = song.length_secs;
+            play.playing_since = song.is_playing.then_some(now);
+            play.last_position = song.position_secs;
+            return None;
+        }
+
+        let finished = self.current.take().and_then(|play| play.finish(now));
+        self.current = song.map(|song| Play {
+            entry: HistoryEntry {
+                started_at: unix_secs(now),
+                ended_at: unix_secs(now),
+                title: song.title.clone(),
+                artist: song.artist.clone(),
+                album: song.album.clone(),
+                length_secs: song.length_secs,
+                played_secs: 0,
+                player: player.map(|p| p.identity.clone()),
+            },
+            started: now,
+            played: Duration::ZERO,
+            playing_since: song.is_playing.then_some(now),
+            bus_name: bus_name.map(str::to_string),
+            last_position: song.position_secs,
+        });
+        finished
+    }
+}
+
+impl Play {
+    // false on a track change, a switch to another player, or the same song starting over
+    // (repeat one, or queued twice in a row)
+    fn continues(&self, song: &SongInfo, bus_name: Option<&str>) -> bool {
+        let length = self.entry.length_secs;
+        let restarted =
+            length > 0 && self.last_position * 5 >= length * 4 && song.position_secs < RESTART_SECS;
+        self.entry.title == song.title
+            && self.entry.artist == song.artist
+            && self.entry.album == song.album
+            && self.bus_name.as_deref() == bus_name
+            && !restarted
+    }
+
+    fn finish(mut self, now: SystemTime) -> Option<HistoryEntry> {
+        if self.played < MIN_PLAYED {
+            return None;
+        }
+        self.entry.started_at = unix_secs(self.started);
+        self.entry.ended_at = unix_secs(now);
+        self.entry.played_secs = self.played.as_secs();
+        Some(self.entry)
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,207 @@
+use super::{History, HistoryEntry, HistorySettings, PlayTracker, unix_now};
+use crate::models::{PlaybackState, PlayerEvent, PlayerSummary, SongInfo};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+fn at(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+fn track(title: &str, is_playing: bool) -> PlayerEvent {
+    PlayerEvent::TrackChanged(SongInfo {
+        title: title.to_string(),
+        artist: "Artist".to_string(),
+        length_secs: 200,
+        is_playing,
+        ..SongInfo::default()
+    })
+}
+
+fn at_position(title: &str, position_secs: u64) -> PlayerEvent {
+    PlayerEvent::TrackChanged(SongInfo {
+        title: title.to_string(),
+        length_secs: 200,
+        position_secs,
+        is_playing: true,
+        ..SongInfo::default()
+    })
+}
+
+fn player(identity: &str, bus_name: &str) -> PlayerSummary {
+    PlayerSummary {
+        identity: identity.to_string(),
+        bus_name: bus_name.to_string(),
+        status: PlaybackState::Playing,
+        followed: true,
+    }
+}
+
+fn entry(title: &str, ended_at: u64) -> HistoryEntry {
+    HistoryEntry {
+        started_at: ended_at - 10,
+        ended_at,
+        title: title.to_string(),
+        artist: String::new(),
+        album: String::new(),
+        length_secs: 10,
+        played_secs: 10,
+        player: None,
+    }
+}
+
+fn titles(entries: &[HistoryEntry]) -> Vec<&str> {
+    entries.iter().map(|e| e.title.as_str()).collect()
+}
+
+#[test]
+fn tracker_counts_only_time_spent_playing() {
+    let mut tracker = PlayTracker::default();
+    let (first, other) = (player("Player", "player"), player("Other", "other"));
+    let player = Some(&first);
+
+    assert_eq!(tracker.observe(&track("One", true), player, at(1000)), None);
+    assert_eq!(tracker.observe(&track("One", true), player, at(1030)), None);
+    assert_eq!(
+        tracker.observe(&track("One", false), player, at(1060)),
+        None
+    );
+    // paused for a while
+    assert_eq!(tracker.observe(&track("One", true), player, at(1500)), None);
+    let entry = tracker
+        .observe(&track("Two", true), Some(&other), at(1520))
+        .unwrap();
+
+    assert_eq!(entry.title, "One");
+    assert_eq!(entry.artist, "Artist");
+    assert_eq!(entry.started_at, 1000);
+    assert_eq!(entry.ended_at, 1520);
+    assert_eq!(entry.played_secs, 80);
+    assert_eq!(entry.length_secs, 200);
+    assert_eq!(entry.player.as_deref(), Some("Player"));
+
+    let entry = tracker
+        .observe(&PlayerEvent::Stopped, None, at(1550))
+        .unwrap();
+    assert_eq!(entry.title, "Two");
+    assert_eq!(entry.player.as_deref(), Some("Other"));
+    assert_eq!(
+        tracker.observe(&PlayerEvent::PlayerGone, None, at(1600)),
+        None
+    );
+}
+
+#[test]
+fn same_song_twice_in_a_row_is_two_plays() {
+    let mut tracker = PlayTracker::default();
+
+    assert_eq!(
+        tracker.observe(&at_position("Loop", 0), None, at(1000)),
+        None
+    );
+    assert_eq!(
+        tracker.observe(&at_position("Loop", 100), None, at(1100)),
+        None
+    );
+    // seeking back early is still the same play
+    assert_eq!(
+        tracker.observe(&at_position("Loop", 2), None, at(1101)),
+        None
+    );
+    assert_eq!(
+        tracker.observe(&at_position("Loop", 195), None, at(1295)),
+        None
+    );
+    let first = tracker
+        .observe(&at_position("Loop", 3), None, at(1300))
+        .unwrap();
+    let second = tracker
+        .observe(&PlayerEvent::Stopped, None, at(1400))
+        .unwrap();
+
+    assert_eq!((first.started_at, first.ended_at), (1000, 1300));
+    assert_eq!((second.started_at, second.ended_at), (1300, 1400));
+    assert_eq!(second.title, "Loop");
+}
+
+#[test]
+fn same_song_on_another_player_is_a_new_play() {
+    let mut tracker = PlayTracker::default();
+    let (one, two) = (player("mpv", "mpv.1"), player("mpv", "mpv.2"));
+
+    tracker.observe(&at_position("Song", 0), Some(&one), at(1000));
+    let entry = tracker
+        .observe(&at_position("Song", 50), Some(&two), at(1050))
+        .unwrap();
+
+    assert_eq!(entry.ended_at, 1050);
+    assert_eq!(entry.player.as_deref(), Some("mpv"));
+}
+
+#[test]
+fn tracker_skips_tracks_that_never_played() {
+    let mut tracker = PlayTracker::default();
+
+    tracker.observe(&track("Paused", false), None, at(1000));
+    assert_eq!(tracker.observe(&track("Next", true), None, at(1100)), None);
+}
+
+#[test]
+fn entries_survive_a_restart() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.jsonl");
+    let now = unix_now();
+
+    let history = History::load(path.clone(), HistorySettings::default());
+    history.record(entry("One", now - 60));
+    history.record(entry("Two", now));
+
+    let mut file = std::fs::read_to_string(&path).unwrap();
+    // torn write from a crash
+    file.push_str("{\"title\": \"Thr");
+    std::fs::write(&path, file).unwrap();
+
+    let reloaded = History::load(path, HistorySettings::default());
+    assert_eq!(titles(&reloaded.query(None, 50)), ["Two", "One"]);
+    assert_eq!(titles(&reloaded.query(Some(now - 30), 50)), ["Two"]);
+    assert_eq!(titles(&reloaded.query(None, 1)), ["Two"]);
+}
+
+#[test]
+fn retention_drops_old_and_excess_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.jsonl");
+    let now = unix_now();
+    let settings = HistorySettings {
+        max_age_days: 1,
+        max_entries: 3,
+        ..HistorySettings::default()
+    };
+
+    let history = History::load(path.clone(), settings.clone());
+    history.record(entry("Ancient", now - 3 * 24 * 60 * 60));
+    for i in 0..40 {
+        history.record(entry(&i.to_string(), now));
+    }
+    assert_eq!(titles(&history.query(None, 50)), ["39", "38", "37"]);
+
+    // stale lines get compacted away eventually
+    let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+    assert!(lines < 41, "{lines} lines left");
+    let reloaded = History::load(path, settings);
+    assert_eq!(titles(&reloaded.query(None, 50)), ["39", "38", "37"]);
+}
+
+#[test]
+fn disabled_history_records_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("history.jsonl");
+    let settings = HistorySettings {
+        enabled: false,
+        ..HistorySettings::default()
+    };
+
+    let history = History::load(path.clone(), settings);
+    history.record(entry("One", unix_now()));
+
+    assert!(history.query(None, 50).is_empty());
+    assert!(!path.exists());
+}
@@ -0,0 +1,77 @@
+use crate::config::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+
+// both next to config.json, so every config dir gets its own server
+pub const LOCK_FILE: &str = "instance.lock";
+pub const INSTANCE_FILE: &str = "instance.json";
+
+// what a second launch needs to reach the running server
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct InstanceInfo {
+    pub pid: u32,
+    pub base_url: String,
+}
+
+pub enum Acquired {
+    Primary(InstanceLock),
+    // None while the other one is still starting up
+    Running(Option<InstanceInfo>),
+}
+
+// held by the serving process. the os drops the lock when it exits, crashes included,
+// so a leftover instance.json never blocks the next start
+pub struct InstanceLock {
+    _file: File,
+    info_path: PathBuf,
+}
+
+impl InstanceLock {
+    pub fn acquire(dir: &Path) -> io::Result<Acquired> {
+        fs::create_dir_all(dir)?;
+        let file = File::options()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(dir.join(LOCK_FILE))?;
+        match file.try_lock() {
+            Ok(()) => Ok(Acquired::Primary(InstanceLock {
+                _file: file,
+                info_path: dir.join(INSTANCE_FILE),
+            })),
+            Err(TryLockError::WouldBlock) => Ok(Acquired::Running(read_info(dir))),
+            Err(TryLockError::Error(e)) => Err(e),
+        }
+    }
+
+    // once the server is about to listen
+    pub fn publish(&self, info: &InstanceInfo) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(info).map_err(io::Error::other)?;
+        write_atomic(&self.info_path, json.as_bytes())
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.info_path);
+    }
+}
+
+// the server using `dir`, if there is one. only peeks, never holds the lock
+pub fn running(dir: &Path) -> Option<InstanceInfo> {
+    let file = File::open(dir.join(LOCK_FILE)).ok()?;
+    match file.try_lock_shared() {
+        Err(TryLockError::WouldBlock) => read_info(dir),
+        _ => None,
+    }
+}
+
+fn read_info(dir: &Path) -> Option<InstanceInfo> {
+    let content = fs::read_to_string(dir.join(INSTANCE_FILE)).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+#[cfg(test)]
+mod tests;
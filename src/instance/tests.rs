@@ -0,0 +1,60 @@
+use super::{Acquired, INSTANCE_FILE, InstanceInfo, InstanceLock, running};
+
+fn info() -> InstanceInfo {
+    InstanceInfo {
+        pid: 42,
+        base_url: "http://127.0.0.1:3333".to_string(),
+    }
+}
+
+#[test]
+fn second_acquire_sees_the_running_instance() {
+    let dir = tempfile::tempdir().unwrap();
+    let Acquired::Primary(lock) = InstanceLock::acquire(dir.path()).unwrap() else {
+        panic!("nothing should be running yet");
+    };
+    assert!(matches!(
+        InstanceLock::acquire(dir.path()).unwrap(),
+        Acquired::Running(None)
+    ));
+
+    lock.publish(&info()).unwrap();
+    assert!(matches!(
+        InstanceLock::acquire(dir.path()).unwrap(),
+        Acquired::Running(Some(found)) if found == info()
+    ));
+    assert_eq!(running(dir.path()), Some(info()));
+}
+
+#[test]
+fn dropping_the_lock_frees_it_and_removes_the_info() {
+    let dir = tempfile::tempdir().unwrap();
+    let Acquired::Primary(lock) = InstanceLock::acquire(dir.path()).unwrap() else {
+        panic!("nothing should be running yet");
+    };
+    lock.publish(&info()).unwrap();
+    drop(lock);
+
+    assert!(!dir.path().join(INSTANCE_FILE).exists());
+    assert_eq!(running(dir.path()), None);
+    assert!(matches!(
+        InstanceLock::acquire(dir.path()).unwrap(),
+        Acquired::Primary(_)
+    ));
+}
+
+#[test]
+fn leftover_info_without_a_lock_holder_is_ignored() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join(INSTANCE_FILE),
+        serde_json::to_string(&info()).unwrap(),
+    )
+    .unwrap();
+
+    assert_eq!(running(dir.path()), None);
+    assert!(matches!(
+        InstanceLock::acquire(dir.path()).unwrap(),
+        Acquired::Primary(_)
+    ));
+}
@@ -1,75 +1,252 @@
 #![cfg_attr(target_os = "windows", windows_subsystem = "windows")]
 
+mod art;
+mod assets;
+mod auth;
+mod cli;
+mod commands;
 mod config;
+mod events;
+mod file_output;
+mod history;
+mod instance;
 mod media_reader;
 mod models;
 mod server;
+mod settings;
+mod template;
+#[cfg(feature = "tray")]
 mod tray;
 
+use crate::art::{ArtFetcher, ArtStore, FetchLimits};
+use crate::assets::StaticFiles;
+use crate::auth::Auth;
+use crate::cli::{Cli, Command, ServeArgs};
 use crate::config::ConfigManager;
-use crate::media_reader::{MediaReader, PlatformMediaReader};
-use crate::models::SongInfo;
+use crate::events::EventLog;
+use crate::file_output::FileOutputFollower;
+use crate::history::History;
+use crate::instance::{Acquired, InstanceInfo, InstanceLock};
+use crate::media_reader::{
+    MediaReader, POLL_INTERVAL, PlatformMediaReader, ScriptedMediaReader, Timeline,
+};
 use crate::server::AppState;
-use crate::tray::TrayCommand;
+use crate::settings::Settings;
+use clap::Parser;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, oneshot};
 
 #[tokio::main]
 async fn main() {
-    let config_manager = ConfigManager::new();
-    let song_info = Arc::new(Mutex::new(None));
+    let cli = Cli::parse();
+    let config_path = config::resolve_config_path(cli.config.as_deref());
+    let result = match cli.command() {
+        Command::Serve(args) => return serve(args, config_path).await,
+        Command::Now { format, json } => commands::now(&config_path, format.as_deref(), json).await,
+        Command::Players { json } => commands::players(&config_path, json).await,
+        Command::Config(command) => commands::config(&config_path, command).await,
+    };
+
+    // built for the windows subsystem, so there is no console unless we borrow the caller's
+    #[cfg(target_os = "windows")]
+    unsafe {
+        use windows::Win32::System::Console::{ATTACH_PARENT_PROCESS, AttachConsole};
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+
+    match result {
+        Ok(output) if output.is_empty() => {}
+        Ok(output) => println!("{output}"),
+        Err(e) => {
+            eprintln!("{e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn serve(args: ServeArgs, config_path: PathBuf) {
+    let settings = Settings::load(&args, &config_path.with_file_name(settings::SETTINGS_FILE));
+    let token_path = config_path.with_file_name(auth::TOKEN_FILE);
+    let instance_dir = config_path.parent().unwrap_or(Path::new("."));
+    let instance = match InstanceLock::acquire(instance_dir) {
+        Ok(Acquired::Primary(instance)) => Some(instance),
+        Ok(Acquired::Running(info)) => hand_off(info, &args, &settings, &token_path),
+        Err(e) => {
+            eprintln!("could not check for a running instance: {e}");
+            None
+        }
+    };
+    let script = match (&args.script, args.demo) {
+        (Some(path), _) => match Timeline::load(path) {
+            Ok(timeline) => Some(timeline),
+            Err(e) => {
+                eprintln!("could not load script {}: {e}", path.display());
+                std::process::exit(1);
+            }
+        },
+        (None, true) => Some(Timeline::demo()),
+        (None, false) => None,
+    };
+
     let (tx, _rx) = broadcast::channel(100);
+    let (reader_tx, reader_rx) = mpsc::channel();
+
+    let output_dir = settings
+        .file_output_dir
+        .clone()
+        .unwrap_or_else(|| config_path.with_file_name(file_output::OUTPUT_DIR));
+    let auth = Auth::load(&settings.auth, &token_path);
+    let history = History::load(
+        config_path.with_file_name(history::HISTORY_FILE),
+        settings.history.clone(),
+    );
 
     let state = Arc::new(AppState {
-        config_manager,
-        song_info: song_info.clone(),
-        tx: tx.clone(),
+        config_manager: ConfigManager::new(config_path, tx.clone()),
+        art: ArtStore::default(),
+        art_fetcher: ArtFetcher::new(art::default_cache_dir(), FetchLimits::default()),
+        events: EventLog::new(&tx),
+        auth,
+        static_files: args
+            .static_dir
+            .map_or(StaticFiles::Embedded, StaticFiles::Dir),
+        history,
+        song_info: Arc::new(Mutex::new(None)),
+        players: Arc::new(Mutex::new(Vec::new())),
+        tx,
+        reader_tx,
     });
 
-    let tray_rx = tray::spawn_tray();
+    // idles until file output is turned on
+    let follower =
+        FileOutputFollower::new(state.config_manager.get_config().file_output, output_dir);
+    file_output::spawn(follower, state.tx.subscribe());
 
-    let tx_clone = tx.clone();
-    let song_info_clone = song_info.clone();
-    std::thread::spawn(move || {
-        let reader = PlatformMediaReader::new();
-        let mut last_info: Option<SongInfo> = None;
-        loop {
-            let current = reader.get_current_song();
-            if current != last_info {
-                if let Some(ref info) = current {
-                    {
-                        let mut lock = song_info_clone.lock().unwrap();
-                        *lock = Some(info.clone());
-                    }
-                    // ws
-                    let _ = tx_clone.send(info.clone());
-                }
-                last_info = current;
-            }
-            std::thread::sleep(Duration::from_secs(1));
+    let reader_state = state.clone();
+    std::thread::spawn(move || match script {
+        Some(timeline) => media_reader::run_reader(
+            ScriptedMediaReader::from_timeline(timeline),
+            &reader_state,
+            reader_rx,
+            POLL_INTERVAL,
+        ),
+        None => media_reader::run_reader(
+            PlatformMediaReader::new(),
+            &reader_state,
+            reader_rx,
+            POLL_INTERVAL,
+        ),
+    });
+
+    let base_url = settings.base_url();
+    // customize needs the token to save
+    let customize_url = format!("{base_url}/customize?token={}", state.auth.token());
+    // fires when quit is picked from the tray
+    let (quit_tx, quit_rx) = oneshot::channel::<()>();
+    if args.headless || !cfg!(feature = "tray") {
+        println!("overlay:   {base_url}/");
+        println!("customize: {customize_url}");
+    } else {
+        #[cfg(feature = "tray")]
+        spawn_tray(base_url, customize_url, quit_tx);
+    }
+    #[cfg(not(feature = "tray"))]
+    let _ = quit_tx;
+
+    let (shutdown_tx, shutdown_rx) = oneshot::channel();
+    tokio::spawn(async move {
+        tokio::select! {
+            _ = shutdown_signal() => {}
+            Ok(()) = quit_rx => {}
         }
+        let _ = shutdown_tx.send(());
     });
 
-    let (shutdown_tx, shutdown_rx) = tokio::sync::oneshot::channel::<()>();
+    if let Some(instance) = &instance {
+        let info = InstanceInfo {
+            pid: std::process::id(),
+            base_url: settings.base_url(),
+        };
+        if let Err(e) = instance.publish(&info) {
+            eprintln!("could not write {}: {e}", instance::INSTANCE_FILE);
+        }
+    }
+
+    let result = server::run_server(state, &settings.bind_address(), shutdown_rx).await;
+    // exit skips destructors
+    drop(instance);
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+// another launch for the same config: show what's already running instead of failing to bind
+fn hand_off(
+    info: Option<InstanceInfo>,
+    args: &ServeArgs,
+    settings: &Settings,
+    token_path: &Path,
+) -> ! {
+    let Some(info) = info else {
+        eprintln!("currentsong is already starting");
+        std::process::exit(1);
+    };
+    let customize_url = format!(
+        "{}/customize?token={}",
+        info.base_url,
+        Auth::load(&settings.auth, token_path).token()
+    );
+    if args.headless || !cfg!(feature = "tray") {
+        println!("already running (pid {})", info.pid);
+        println!("overlay:   {}/", info.base_url);
+        println!("customize: {customize_url}");
+    } else if let Err(e) = open::that(&customize_url) {
+        eprintln!(
+            "already running at {}, could not open it: {e}",
+            info.base_url
+        );
+    }
+    std::process::exit(0);
+}
+
+// ctrl-c, or SIGTERM from systemd and friends
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{SignalKind, signal};
+        let mut terminate = signal(SignalKind::terminate()).expect("SIGTERM handler");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+}
+
+#[cfg(feature = "tray")]
+fn spawn_tray(base_url: String, customize_url: String, quit_tx: oneshot::Sender<()>) {
+    use crate::tray::TrayCommand;
 
+    let tray_rx = tray::spawn_tray(base_url.clone());
     std::thread::spawn(move || {
         while let Ok(cmd) = tray_rx.recv() {
             match cmd {
                 TrayCommand::Preview => {
-                    let _ = open::that("http://127.0.0.1:3333/");
+                    let _ = open::that(format!("{base_url}/"));
                 }
                 TrayCommand::OpenCustomize => {
-                    let _ = open::that("http://127.0.0.1:3333/customize");
+                    let _ = open::that(&customize_url);
                 }
                 TrayCommand::Quit => {
-                    let _ = shutdown_tx.send(());
-                    std::thread::sleep(Duration::from_millis(500));
+                    let _ = quit_tx.send(());
+                    std::thread::sleep(std::time::Duration::from_millis(500));
                     std::process::exit(0);
                 }
             }
         }
     });
-
-    server::run_server(state, shutdown_rx).await;
 }
@@ -1,12 +1,21 @@
-use crate::media_reader::MediaReader;
-use crate::models::SongInfo;
-use base64::{Engine as _, engine::general_purpose};
-use mpris::{Metadata, PlayerFinder};
+use crate::media_reader::{
+    ControlError, MAX_SEEK_SECS, MediaController, MediaReader, PlayerSelection,
+};
+use crate::models::{PlaybackState, PlayerEvent, PlayerSummary, SongInfo};
+use dbus::blocking::Connection;
+use dbus::message::MatchRule;
+use mpris::{Metadata, Player, PlayerFinder};
 use std::cell::RefCell;
 use std::fs::File;
 use std::io::Read;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, mpsc};
+use std::time::Duration;
+
+const MPRIS_PATH: &str = "/org/mpris/MediaPlayer2";
+const MPRIS_PLAYER_INTERFACE: &str = "org.mpris.MediaPlayer2.Player";
+const MPRIS_BUS_PREFIX: &str = "org.mpris.MediaPlayer2.";
 
 // CACHING
 struct CachedTrack {
@@ -16,33 +25,163 @@ struct CachedTrack {
     album: String,
     length_secs: u64,
     art_url: Option<String>,
-    album_art_base64: Option<Arc<String>>,
+    album_art: Option<Arc<Vec<u8>>>,
 }
 
 pub struct LinuxMediaReader {
     player_finder: PlayerFinder,
+    selection: RefCell<PlayerSelection>,
+    players: RefCell<Vec<PlayerSummary>>,
     cached_track: RefCell<Option<CachedTrack>>,
 
     tracked_pos: RefCell<f64>,
     last_tick: RefCell<Option<std::time::Instant>>,
     last_reported_pos: RefCell<f64>,
+    // set by the signal watcher on Seeked and by poll_once, trust the reported position once
+    seeked: Arc<AtomicBool>,
 }
 
 impl MediaReader for LinuxMediaReader {
     fn new() -> Self {
-        Self {
-            player_finder: PlayerFinder::new().expect("Could not connect to D-Bus"),
+        Self::try_new().expect("Could not connect to D-Bus")
+    }
+
+    fn try_new() -> Result<Self, String> {
+        let player_finder =
+            PlayerFinder::new().map_err(|e| format!("could not connect to D-Bus: {e}"))?;
+        Ok(Self {
+            player_finder,
+            selection: RefCell::new(PlayerSelection::default()),
+            players: RefCell::new(Vec::new()),
             cached_track: RefCell::new(None),
             tracked_pos: RefCell::new(0.0),
             last_tick: RefCell::new(None),
             last_reported_pos: RefCell::new(0.0),
+            seeked: Arc::new(AtomicBool::new(false)),
+        })
+    }
+
+    fn poll(&self) -> PlayerEvent {
+        let Some(player) = self.select_player() else {
+            *self.cached_track.borrow_mut() = None;
+            return PlayerEvent::PlayerGone;
+        };
+
+        let status = player.get_playback_status();
+        if matches!(status, Ok(mpris::PlaybackStatus::Stopped)) {
+            *self.cached_track.borrow_mut() = None;
+            return PlayerEvent::Stopped;
+        }
+
+        match self.read_song(&player, status) {
+            Some(info) => PlayerEvent::TrackChanged(info),
+            None => {
+                // CLEAR
+                *self.cached_track.borrow_mut() = None;
+                PlayerEvent::Stopped
+            }
         }
     }
 
-    fn get_current_song(&self) -> Option<SongInfo> {
-        if let Ok(player) = self.player_finder.find_active()
-            && let Ok(metadata) = player.get_metadata()
-        {
+    // a new song otherwise starts at 0:00 or 0:01 in case the player still reports the old one
+    fn poll_once(&self) -> PlayerEvent {
+        self.seeked.store(true, Ordering::Relaxed);
+        self.poll()
+    }
+
+    fn set_player_selection(&self, selection: PlayerSelection) {
+        *self.selection.borrow_mut() = selection;
+    }
+
+    fn list_players(&self) -> Vec<PlayerSummary> {
+        self.players.borrow().clone()
+    }
+
+    fn watch_changes(&self, notify: Arc<dyn Fn() + Send + Sync>, watching: Arc<AtomicBool>) {
+        let seeked = self.seeked.clone();
+        let (ready_tx, ready_rx) = mpsc::channel();
+
+        // own connection, the finder's one is not Send
+        std::thread::spawn(move || {
+            let conn = match subscribe_signals(notify, seeked) {
+                Ok(conn) => {
+                    watching.store(true, Ordering::Relaxed);
+                    let _ = ready_tx.send(());
+                    conn
+                }
+                Err(e) => {
+                    eprintln!("MPRIS signals unavailable, polling only: {e}");
+                    let _ = ready_tx.send(());
+                    return;
+                }
+            };
+
+            while conn.process(Duration::from_secs(60)).is_ok() {}
+            watching.store(false, Ordering::Relaxed);
+            eprintln!("lost D-Bus connection, polling only");
+        });
+
+        // so the first pass already knows
+        let _ = ready_rx.recv();
+    }
+}
+
+fn subscribe_signals(
+    notify: Arc<dyn Fn() + Send + Sync>,
+    seeked: Arc<AtomicBool>,
+) -> Result<Connection, dbus::Error> {
+    let conn = Connection::new_session()?;
+
+    // metadata, playback status, volume...
+    let on_change = notify.clone();
+    conn.add_match(
+        MatchRule::new_signal("org.freedesktop.DBus.Properties", "PropertiesChanged")
+            .with_path(MPRIS_PATH),
+        move |(): (), _, msg| {
+            if msg
+                .read1::<&str>()
+                .is_ok_and(|i| i == MPRIS_PLAYER_INTERFACE)
+            {
+                on_change();
+            }
+            true
+        },
+    )?;
+
+    let on_seek = notify.clone();
+    conn.add_match(
+        MatchRule::new_signal(MPRIS_PLAYER_INTERFACE, "Seeked").with_path(MPRIS_PATH),
+        move |(): (), _, _| {
+            seeked.store(true, Ordering::Relaxed);
+            on_seek();
+            true
+        },
+    )?;
+
+    // players opening and closing
+    conn.add_match(
+        MatchRule::new_signal("org.freedesktop.DBus", "NameOwnerChanged"),
+        move |(): (), _, msg| {
+            if msg
+                .read1::<&str>()
+                .is_ok_and(|name| name.starts_with(MPRIS_BUS_PREFIX))
+            {
+                notify();
+            }
+            true
+        },
+    )?;
+
+    Ok(conn)
+}
+
+impl LinuxMediaReader {
+    fn read_song(
+        &self,
+        player: &Player,
+        status: Result<mpris::PlaybackStatus, mpris::DBusError>,
+    ) -> Option<SongInfo> {
+        if let Ok(metadata) = player.get_metadata() {
             let current_id = metadata.track_id().map(|id| id.to_string());
 
             let title = metadata.title().unwrap_or("Unknown Title").to_string();
@@ -56,8 +195,7 @@ impl MediaReader for LinuxMediaReader {
                 .map(|d| d.as_secs_f64())
                 .unwrap_or(0.0);
 
-            let is_playing = player
-                .get_playback_status()
+            let is_playing = status
                 .map(|s| s == mpris::PlaybackStatus::Playing)
                 .unwrap_or(false);
 
@@ -72,12 +210,13 @@ impl MediaReader for LinuxMediaReader {
             let is_new_song = cached
                 .as_ref()
                 .is_none_or(|c| c.id != current_id || c.title != title || c.artist != artist);
+            let seeked = self.seeked.swap(false, Ordering::Relaxed);
 
             if is_new_song {
                 let album = metadata.album_name().unwrap_or("").to_string();
                 let length_secs = metadata.length().map(|d| d.as_secs()).unwrap_or(0);
                 let art_url = metadata.art_url().map(|s| s.to_string());
-                let album_art_base64 = get_album_art_base64(&metadata).map(Arc::new);
+                let album_art = get_album_art(&metadata).map(Arc::new);
 
                 *cached = Some(CachedTrack {
                     id: current_id,
@@ -86,10 +225,14 @@ impl MediaReader for LinuxMediaReader {
                     album,
                     length_secs,
                     art_url,
-                    album_art_base64,
+                    album_art,
                 });
 
-                *tracked_pos = reported_pos.min(1.0);
+                *tracked_pos = if seeked {
+                    reported_pos
+                } else {
+                    reported_pos.min(1.0)
+                };
                 *last_reported = reported_pos;
                 *last_tick = Some(now);
             } else {
@@ -98,7 +241,7 @@ impl MediaReader for LinuxMediaReader {
                 if let Some(ref mut c) = *cached
                     && c.art_url != current_art_url
                 {
-                    c.album_art_base64 = get_album_art_base64(&metadata).map(Arc::new);
+                    c.album_art = get_album_art(&metadata).map(Arc::new);
                     c.art_url = current_art_url;
                 }
 
@@ -111,7 +254,7 @@ impl MediaReader for LinuxMediaReader {
                 let diff = reported_pos - *last_reported;
                 *last_reported = reported_pos;
 
-                if reported_pos < 1.0 || ((diff - dt).abs() > 3.0 && *tracked_pos > 2.0) {
+                if seeked || reported_pos < 1.0 || ((diff - dt).abs() > 3.0 && *tracked_pos > 2.0) {
                     *tracked_pos = reported_pos;
                 } else if is_playing {
                     *tracked_pos += dt;
@@ -130,20 +273,112 @@ impl MediaReader for LinuxMediaReader {
                 title: track.title.clone(),
                 artist: track.artist.clone(),
                 album: track.album.clone(),
-                album_art_base64: track.album_art_base64.clone(),
+                album_art: track.album_art.clone(),
+                album_art_source: track.art_url.clone(),
                 position_secs,
                 length_secs: track.length_secs,
                 is_playing,
+                ..SongInfo::default()
             });
         }
 
-        // CLEAR
-        *self.cached_track.borrow_mut() = None;
         None
     }
 }
 
-fn get_album_art_base64(metadata: &Metadata) -> Option<String> {
+impl MediaController for LinuxMediaReader {
+    fn play_pause(&self) -> Result<(), ControlError> {
+        let player = self.active_player()?;
+        checked(player.checked_play_pause())
+    }
+
+    fn next(&self) -> Result<(), ControlError> {
+        let player = self.active_player()?;
+        checked(player.checked_next())
+    }
+
+    fn previous(&self) -> Result<(), ControlError> {
+        let player = self.active_player()?;
+        checked(player.checked_previous())
+    }
+
+    fn seek_to(&self, position_secs: f64) -> Result<(), ControlError> {
+        let player = self.active_player()?;
+        let track_id = player
+            .get_metadata()
+            .map_err(|e| ControlError::Failed(e.to_string()))?
+            .track_id()
+            .ok_or(ControlError::Unsupported)?;
+
+        let position = mpris_position(position_secs)?;
+        checked(player.checked_set_position(track_id, &position))?;
+
+        // jump the extrapolated position instead of waiting for the next poll to notice
+        *self.tracked_pos.borrow_mut() = position_secs;
+        *self.last_reported_pos.borrow_mut() = position_secs;
+        *self.last_tick.borrow_mut() = Some(std::time::Instant::now());
+        Ok(())
+    }
+
+    fn set_volume(&self, volume: f64) -> Result<(), ControlError> {
+        let player = self.active_player()?;
+        checked(player.checked_set_volume(volume.clamp(0.0, 1.0)))
+    }
+}
+
+// what set_position can take without its i64 microseconds overflowing
+pub(super) fn mpris_position(position_secs: f64) -> Result<Duration, ControlError> {
+    if !(0.0..=MAX_SEEK_SECS).contains(&position_secs) {
+        return Err(ControlError::Failed(format!(
+            "can't seek to {position_secs}s"
+        )));
+    }
+    Duration::try_from_secs_f64(position_secs).map_err(|e| ControlError::Failed(e.to_string()))
+}
+
+impl LinuxMediaReader {
+    fn active_player(&self) -> Result<Player, ControlError> {
+        self.select_player().ok_or(ControlError::NoPlayer)
+    }
+
+    // picks the player to follow and remembers every player seen for list_players
+    fn select_player(&self) -> Option<Player> {
+        let mut players = self.player_finder.find_all().unwrap_or_default();
+
+        let mut summaries: Vec<PlayerSummary> = players
+            .iter()
+            .map(|player| PlayerSummary {
+                identity: player.identity().to_string(),
+                bus_name: player.bus_name().to_string(),
+                status: match player.get_playback_status() {
+                    Ok(mpris::PlaybackStatus::Playing) => PlaybackState::Playing,
+                    Ok(mpris::PlaybackStatus::Paused) => PlaybackState::Paused,
+                    _ => PlaybackState::Stopped,
+                },
+                followed: false,
+            })
+            .collect();
+
+        let picked = self.selection.borrow().pick(&summaries);
+        if let Some(index) = picked {
+            summaries[index].followed = true;
+        }
+        *self.players.borrow_mut() = summaries;
+
+        picked.map(|index| players.swap_remove(index))
+    }
+}
+
+// mpris checked_* calls return Ok(false) when the player says it can't
+fn checked(result: Result<bool, mpris::DBusError>) -> Result<(), ControlError> {
+    match result {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ControlError::Unsupported),
+        Err(e) => Err(ControlError::Failed(e.to_string())),
+    }
+}
+
+fn get_album_art(metadata: &Metadata) -> Option<Vec<u8>> {
     if let Some(art_url) = metadata.art_url() {
         let path_str = art_url.strip_prefix("file://")?;
 
@@ -154,7 +389,7 @@ fn get_album_art_base64(metadata: &Metadata) -> Option<String> {
         {
             let mut buffer = Vec::new();
             if file.read_to_end(&mut buffer).is_ok() {
-                return Some(general_purpose::STANDARD.encode(&buffer));
+                return Some(buffer);
             }
         }
     }
@@ -1,8 +1,189 @@
-use crate::models::SongInfo;
+use crate::history::PlayTracker;
+use crate::models::{PlayerCommand, PlayerEvent, PlayerSummary, ServerMessage};
+use crate::server::AppState;
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+use tokio::sync::oneshot;
+
+mod scripted;
+mod selection;
+
+pub use scripted::{ScriptedMediaReader, Timeline};
+pub use selection::PlayerSelection;
+
+pub const POLL_INTERVAL: Duration = Duration::from_secs(1);
+// fallback when nothing is playing and the backend pushes changes
+const IDLE_POLL_FACTOR: u32 = 5;
+// mpris takes the position as i64 microseconds, anything past this wraps or overflows
+pub const MAX_SEEK_SECS: f64 = (i64::MAX / 1_000_000) as f64;
 
 pub trait MediaReader {
     fn new() -> Self;
-    fn get_current_song(&self) -> Option<SongInfo>;
+    // for one shot callers, an Err instead of a panic when the backend can't be reached
+    fn try_new() -> Result<Self, String>
+    where
+        Self: Sized,
+    {
+        Ok(Self::new())
+    }
+    fn poll(&self) -> PlayerEvent;
+    // a single look with no polls before it, the position is taken as the player reports it
+    fn poll_once(&self) -> PlayerEvent {
+        self.poll()
+    }
+
+    fn set_player_selection(&self, selection: PlayerSelection);
+    // every player seen on the last poll
+    fn list_players(&self) -> Vec<PlayerSummary>;
+
+    // call `notify` whenever the player state changes so the reader polls right away
+    // instead of waiting for the next tick. `watching` stays set for as long as that works,
+    // backends that can't leave it unset and are polled only.
+    fn watch_changes(&self, _notify: Arc<dyn Fn() + Send + Sync>, _watching: Arc<AtomicBool>) {}
+}
+
+pub trait MediaController {
+    fn play_pause(&self) -> Result<(), ControlError>;
+    fn next(&self) -> Result<(), ControlError>;
+    fn previous(&self) -> Result<(), ControlError>;
+    fn seek_to(&self, position_secs: f64) -> Result<(), ControlError>;
+    fn set_volume(&self, volume: f64) -> Result<(), ControlError>;
+
+    fn execute(&self, command: &PlayerCommand) -> Result<(), ControlError> {
+        match *command {
+            PlayerCommand::PlayPause => self.play_pause(),
+            PlayerCommand::Next => self.next(),
+            PlayerCommand::Previous => self.previous(),
+            PlayerCommand::SeekTo { position_secs } => self.seek_to(position_secs),
+            PlayerCommand::SetVolume { volume } => self.set_volume(volume),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ControlError {
+    NoPlayer,
+    // the player (or platform) can't do this
+    Unsupported,
+    Failed(String),
+}
+
+impl fmt::Display for ControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ControlError::NoPlayer => write!(f, "no active player"),
+            ControlError::Unsupported => write!(f, "not supported by the player"),
+            ControlError::Failed(e) => write!(f, "player error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ControlError {}
+
+// wakes the reader thread, which owns the (non Send) player handles
+pub enum ReaderMessage {
+    Control(ControlRequest),
+    // from watch_changes
+    Changed,
+}
+
+pub struct ControlRequest {
+    pub command: PlayerCommand,
+    // None for fire and forget, e.g. from /ws
+    pub reply: Option<oneshot::Sender<Result<(), ControlError>>>,
+}
+
+// polls `reader` forever, publishing changes to the app state and /ws
+pub fn run_reader<R: MediaReader + MediaController>(
+    reader: R,
+    state: &AppState,
+    reader_rx: Receiver<ReaderMessage>,
+    poll_interval: Duration,
+) {
+    let reader_tx = state.reader_tx.clone();
+    // checked every pass, the watcher can lose its connection later on
+    let watching = Arc::new(AtomicBool::new(false));
+    reader.watch_changes(
+        Arc::new(move || {
+            let _ = reader_tx.send(ReaderMessage::Changed);
+        }),
+        watching.clone(),
+    );
+
+    let mut last_event: Option<PlayerEvent> = None;
+    let mut tracker = PlayTracker::default();
+    loop {
+        let config = state.config_manager.get_config();
+        reader.set_player_selection(PlayerSelection::from_config(&config));
+        let mut event = reader.poll();
+        if let PlayerEvent::TrackChanged(song) = &mut event {
+            if song.album_art.is_none()
+                && let Some(source) = &song.album_art_source
+            {
+                let reader_tx = state.reader_tx.clone();
+                song.album_art = state.art_fetcher.lookup(source, move || {
+                    let _ = reader_tx.send(ReaderMessage::Changed);
+                });
+            }
+            state.art.attach(song, &config);
+        }
+        let players = reader.list_players();
+        let followed = players.iter().find(|p| p.followed);
+        if let Some(play) = tracker.observe(&event, followed, SystemTime::now()) {
+            state.history.record(play);
+        }
+        {
+            let mut lock = state.players.lock().unwrap();
+            if *lock != players {
+                *lock = players.clone();
+                let _ = state.tx.send(ServerMessage::Players(players));
+            }
+        }
+        if last_event.as_ref() != Some(&event) {
+            {
+                let mut lock = state.song_info.lock().unwrap();
+                *lock = event.song().cloned();
+            }
+            // ws and sse
+            let _ = state.tx.send(ServerMessage::Song(event.clone()));
+            last_event = Some(event);
+        }
+
+        let is_playing = last_event
+            .as_ref()
+            .and_then(PlayerEvent::song)
+            .is_some_and(|info| info.is_playing);
+        let interval = poll_delay(poll_interval, watching.load(Ordering::Relaxed), is_playing);
+
+        // wait for the next poll, running control requests as they come in
+        let deadline = Instant::now() + interval;
+        while let Some(timeout) = deadline.checked_duration_since(Instant::now()) {
+            match reader_rx.recv_timeout(timeout) {
+                Ok(ReaderMessage::Control(request)) => {
+                    let result = reader.execute(&request.command);
+                    if let Some(reply) = request.reply {
+                        let _ = reply.send(result);
+                    }
+                    // show the effect right away
+                    break;
+                }
+                Ok(ReaderMessage::Changed) | Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => std::thread::sleep(timeout),
+            }
+        }
+    }
+}
+
+// with change signals only a playing track needs ticking, for the position
+fn poll_delay(poll_interval: Duration, watching: bool, is_playing: bool) -> Duration {
+    if watching && !is_playing {
+        poll_interval * IDLE_POLL_FACTOR
+    } else {
+        poll_interval
+    }
 }
 
 #[cfg(target_os = "linux")]
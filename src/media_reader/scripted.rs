@@ -0,0 +1,338 @@
+use crate::art::is_remote;
+use crate::media_reader::{ControlError, MediaController, MediaReader, PlayerSelection};
+use crate::models::{PlaybackState, PlayerEvent, PlayerSummary, SongInfo};
+use serde::Deserialize;
+use std::cell::RefCell;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const DEMO_TIMELINE: &str = include_str!("demo_timeline.json");
+
+const IDENTITY: &str = "Scripted";
+const BUS_NAME: &str = "scripted";
+
+// a script, e.g.
+// {"loop_after_ms": 60000, "steps": [
+//   {"at_ms": 0, "action": "play", "track": {"title": "...", "artist": "...", "length_secs": 200}},
+//   {"at_ms": 5000, "action": "pause"}, {"at_ms": 8000, "action": "resume"},
+//   {"at_ms": 9000, "action": "seek", "position_secs": 120},
+//   {"at_ms": 15000, "action": "stop"}, {"at_ms": 20000, "action": "gone"}]}
+#[derive(Debug, Clone, Deserialize)]
+pub struct Timeline {
+    // start over this long after the beginning, plays once if unset
+    #[serde(default)]
+    pub loop_after_ms: Option<u64>,
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Step {
+    pub at_ms: u64,
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum Action {
+    Play { track: ScriptedTrack },
+    Pause,
+    Resume,
+    Seek { position_secs: f64 },
+    // player stays, nothing loaded
+    Stop,
+    // player quits
+    Gone,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ScriptedTrack {
+    pub title: String,
+    #[serde(default)]
+    pub artist: String,
+    #[serde(default)]
+    pub album: String,
+    #[serde(default)]
+    pub length_secs: u64,
+    #[serde(default)]
+    pub start_secs: f64,
+    // image file relative to the script, or an http(s) url
+    #[serde(default)]
+    pub art: Option<String>,
+    #[serde(skip)]
+    album_art: Option<Arc<Vec<u8>>>,
+}
+
+impl Timeline {
+    pub fn parse(json: &str, base_dir: &Path) -> io::Result<Timeline> {
+        let mut timeline: Timeline = serde_json::from_str(json)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        timeline.steps.sort_by_key(|step| step.at_ms);
+        for step in &mut timeline.steps {
+            if let Action::Play { track } = &mut step.action
+                && let Some(art) = &track.art
+                && !is_remote(art)
+            {
+                let path = base_dir.join(art);
+                let bytes = std::fs::read(&path)
+                    .map_err(|e| io::Error::new(e.kind(), format!("{}: {e}", path.display())))?;
+                track.album_art = Some(Arc::new(bytes));
+            }
+        }
+        Ok(timeline)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Timeline> {
+        let json = std::fs::read_to_string(path)?;
+        Timeline::parse(&json, path.parent().unwrap_or(Path::new(".")))
+    }
+
+    // a few made up tracks, for previewing themes without music
+    pub fn demo() -> Timeline {
+        Timeline::parse(DEMO_TIMELINE, Path::new(".")).expect("demo timeline is valid")
+    }
+}
+
+enum ScriptState {
+    Gone,
+    Stopped,
+    Track {
+        track: ScriptedTrack,
+        playing: bool,
+        // where playback was at `anchor`
+        position_secs: f64,
+        anchor: Instant,
+    },
+}
+
+impl ScriptState {
+    fn position_at(&self, now: Instant) -> f64 {
+        match self {
+            ScriptState::Track {
+                track,
+                playing,
+                position_secs,
+                anchor,
+            } => {
+                let mut position = *position_secs;
+                if *playing {
+                    position += now.saturating_duration_since(*anchor).as_secs_f64();
+                }
+                if track.length_secs > 0 {
+                    position = position.min(track.length_secs as f64);
+                }
+                position.max(0.0)
+            }
+            _ => 0.0,
+        }
+    }
+
+    fn apply(&mut self, action: &Action, at: Instant) {
+        let position = self.position_at(at);
+        match (action, self) {
+            (Action::Play { track }, state) => {
+                *state = ScriptState::Track {
+                    track: track.clone(),
+                    playing: true,
+                    position_secs: track.start_secs,
+                    anchor: at,
+                };
+            }
+            (
+                Action::Pause | Action::Resume,
+                ScriptState::Track {
+                    playing,
+                    position_secs,
+                    anchor,
+                    ..
+                },
+            ) => {
+                *playing = matches!(action, Action::Resume);
+                *position_secs = position;
+                *anchor = at;
+            }
+            (
+                Action::Seek {
+                    position_secs: target,
+                },
+                ScriptState::Track {
+                    position_secs,
+                    anchor,
+                    ..
+                },
+            ) => {
+                *position_secs = *target;
+                *anchor = at;
+            }
+            (Action::Stop, state) => *state = ScriptState::Stopped,
+            (Action::Gone, state) => *state = ScriptState::Gone,
+            // pause/resume/seek with nothing loaded
+            _ => {}
+        }
+    }
+}
+
+struct Playback {
+    // when the current run of the timeline started
+    started: Instant,
+    next_step: usize,
+    state: ScriptState,
+}
+
+// plays back a timeline in real time, controls act on it like on a real player
+pub struct ScriptedMediaReader {
+    timeline: Timeline,
+    selection: RefCell<PlayerSelection>,
+    playback: RefCell<Playback>,
+}
+
+impl ScriptedMediaReader {
+    pub fn from_timeline(timeline: Timeline) -> Self {
+        ScriptedMediaReader {
+            timeline,
+            selection: RefCell::new(PlayerSelection::default()),
+            playback: RefCell::new(Playback {
+                started: Instant::now(),
+                next_step: 0,
+                state: ScriptState::Gone,
+            }),
+        }
+    }
+
+    // what a poll at `now` reports, steps up to then take effect
+    pub fn poll_at(&self, now: Instant) -> PlayerEvent {
+        self.advance(now);
+        if !self.is_followed() {
+            return PlayerEvent::PlayerGone;
+        }
+        let playback = self.playback.borrow();
+        match &playback.state {
+            ScriptState::Gone => PlayerEvent::PlayerGone,
+            ScriptState::Stopped => PlayerEvent::Stopped,
+            ScriptState::Track { track, playing, .. } => PlayerEvent::TrackChanged(SongInfo {
+                title: track.title.clone(),
+                artist: track.artist.clone(),
+                album: track.album.clone(),
+                album_art: track.album_art.clone(),
+                album_art_source: track.art.clone(),
+                position_secs: playback.state.position_at(now) as u64,
+                length_secs: track.length_secs,
+                is_playing: *playing,
+                ..SongInfo::default()
+            }),
+        }
+    }
+
+    fn advance(&self, now: Instant) {
+        let mut playback = self.playback.borrow_mut();
+        loop {
+            let Some(step) = self.timeline.steps.get(playback.next_step) else {
+                // out of steps, start over once the loop is due
+                match self.timeline.loop_after_ms.map(Duration::from_millis) {
+                    Some(period) if !period.is_zero() && playback.started + period <= now => {
+                        playback.started += period;
+                        playback.next_step = 0;
+                        playback.state = ScriptState::Gone;
+                        continue;
+                    }
+                    _ => return,
+                }
+            };
+            let at = playback.started + Duration::from_millis(step.at_ms);
+            if at > now {
+                return;
+            }
+            playback.state.apply(&step.action, at);
+            playback.next_step += 1;
+        }
+    }
+
+    fn summary(&self) -> Option<PlayerSummary> {
+        let status = match &self.playback.borrow().state {
+            ScriptState::Gone => return None,
+            ScriptState::Stopped => PlaybackState::Stopped,
+            ScriptState::Track { playing: true, .. } => PlaybackState::Playing,
+            ScriptState::Track { playing: false, .. } => PlaybackState::Paused,
+        };
+        Some(PlayerSummary {
+            identity: IDENTITY.to_string(),
+            bus_name: BUS_NAME.to_string(),
+            status,
+            followed: false,
+        })
+    }
+
+    // the scripted player can still be ignored through the config
+    fn is_followed(&self) -> bool {
+        let summaries: Vec<_> = self.summary().into_iter().collect();
+        self.selection.borrow().pick(&summaries).is_some()
+    }
+
+    fn control(&self, action: Action) -> Result<(), ControlError> {
+        let now = Instant::now();
+        self.advance(now);
+        if !self.is_followed() {
+            return Err(ControlError::NoPlayer);
+        }
+        self.playback.borrow_mut().state.apply(&action, now);
+        Ok(())
+    }
+}
+
+impl MediaReader for ScriptedMediaReader {
+    fn new() -> Self {
+        ScriptedMediaReader::from_timeline(Timeline::demo())
+    }
+
+    fn poll(&self) -> PlayerEvent {
+        self.poll_at(Instant::now())
+    }
+
+    fn set_player_selection(&self, selection: PlayerSelection) {
+        *self.selection.borrow_mut() = selection;
+    }
+
+    fn list_players(&self) -> Vec<PlayerSummary> {
+        let followed = self.is_followed();
+        self.summary()
+            .map(|summary| PlayerSummary {
+                followed,
+                ..summary
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+impl MediaController for ScriptedMediaReader {
+    fn play_pause(&self) -> Result<(), ControlError> {
+        let playing = matches!(
+            self.playback.borrow().state,
+            ScriptState::Track { playing: true, .. }
+        );
+        self.control(if playing {
+            Action::Pause
+        } else {
+            Action::Resume
+        })
+    }
+
+    // the timeline decides what plays next
+    fn next(&self) -> Result<(), ControlError> {
+        Err(ControlError::Unsupported)
+    }
+
+    fn previous(&self) -> Result<(), ControlError> {
+        Err(ControlError::Unsupported)
+    }
+
+    fn seek_to(&self, position_secs: f64) -> Result<(), ControlError> {
+        self.control(Action::Seek { position_secs })
+    }
+
+    fn set_volume(&self, _volume: f64) -> Result<(), ControlError> {
+        Err(ControlError::Unsupported)
+    }
+}
@@ -0,0 +1,76 @@
+use crate::models::{OverlayConfig, PlayerSummary};
+
+// which player the reader follows when several are open
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerSelection {
+    pub preferred: Option<String>,
+    pub ignored: Vec<String>,
+    pub priority: Vec<String>,
+}
+
+impl PlayerSelection {
+    pub fn from_config(config: &OverlayConfig) -> Self {
+        Self {
+            preferred: config.preferred_player.clone().filter(|p| !p.is_empty()),
+            ignored: config.ignored_players.clone(),
+            priority: config.player_priority.clone(),
+        }
+    }
+
+    // the preferred player whenever it is open, otherwise playing > paused > stopped,
+    // ties broken by the priority list and then bus order
+    pub fn pick(&self, players: &[PlayerSummary]) -> Option<usize> {
+        let candidates = players
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| !self.ignored.iter().any(|pattern| matches(pattern, p)));
+
+        if let Some(preferred) = &self.preferred
+            && let Some((index, _)) = candidates.clone().find(|(_, p)| matches(preferred, p))
+        {
+            return Some(index);
+        }
+
+        candidates
+            .min_by_key(|(index, p)| (p.status, self.priority_rank(p), *index))
+            .map(|(index, _)| index)
+    }
+
+    fn priority_rank(&self, player: &PlayerSummary) -> usize {
+        self.priority
+            .iter()
+            .position(|pattern| matches(pattern, player))
+            .unwrap_or(self.priority.len())
+    }
+}
+
+fn matches(pattern: &str, player: &PlayerSummary) -> bool {
+    glob_match(pattern, &player.identity) || glob_match(pattern, &player.bus_name)
+}
+
+// case insensitive, `*` matches any run of characters
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -1,4 +1,6 @@
-use crate::models::SongInfo;
+use super::{ControlError, MediaController};
+use crate::models::{OverlayConfig, PlayerCommand, PlayerEvent, ServerMessage, SongInfo};
+use std::cell::RefCell;
 
 // SONG INFO
 
@@ -21,7 +23,13 @@ fn song_info_serialization_roundtrip() {
         title: "Test Song".to_string(),
         artist: "Test Artist".to_string(),
         album: "Test Album".to_string(),
+        album_art_url: Some("/api/art/abc".to_string()),
+        album_art_background_url: Some("/api/art/def".to_string()),
+        album_art_mime: Some("image/jpeg".to_string()),
+        album_art_palette: None,
         album_art_base64: Some(std::sync::Arc::new("dGVzdA==".to_string())),
+        album_art: None,
+        album_art_source: None,
         position_secs: 42,
         length_secs: 180,
         is_playing: true,
@@ -42,7 +50,13 @@ fn song_info_json_structure_is_stable() {
     assert!(json.get("title").is_some());
     assert!(json.get("artist").is_some());
     assert!(json.get("album").is_some());
+    assert!(json.get("album_art_url").is_some());
+    assert!(json.get("album_art_background_url").is_some());
+    assert!(json.get("album_art_mime").is_some());
+    assert!(json.get("album_art_palette").is_some());
     assert!(json.get("album_art_base64").is_some());
+    // raw bytes never go over the wire
+    assert!(json.get("album_art").is_none());
     assert!(json.get("position_secs").is_some());
     assert!(json.get("length_secs").is_some());
     assert!(json.get("is_playing").is_some());
@@ -54,7 +68,13 @@ fn song_info_clone_is_independent() {
         title: "Original".to_string(),
         artist: "Artist".to_string(),
         album: "Album".to_string(),
+        album_art_url: None,
+        album_art_background_url: None,
+        album_art_mime: None,
+        album_art_palette: None,
         album_art_base64: Some(std::sync::Arc::new("abc".to_string())),
+        album_art: None,
+        album_art_source: None,
         position_secs: 10,
         length_secs: 200,
         is_playing: false,
@@ -77,19 +97,146 @@ fn song_info_clone_is_independent() {
     assert!(cloned.is_playing);
 }
 
+// PLAYER EVENT
+
+#[test]
+fn player_event_track_changed_carries_song() {
+    let info = SongInfo {
+        title: "Test Song".to_string(),
+        ..SongInfo::default()
+    };
+    let json = serde_json::to_value(PlayerEvent::TrackChanged(info.clone())).unwrap();
+
+    assert_eq!(json["type"], "track_changed");
+    assert_eq!(json["song"]["title"], "Test Song");
+    assert_eq!(PlayerEvent::TrackChanged(info.clone()).song(), Some(&info));
+}
+
+#[test]
+fn player_event_stop_variants_have_no_song() {
+    let stopped = serde_json::to_value(PlayerEvent::Stopped).unwrap();
+    let gone = serde_json::to_value(PlayerEvent::PlayerGone).unwrap();
+
+    assert_eq!(stopped, serde_json::json!({ "type": "stopped" }));
+    assert_eq!(gone, serde_json::json!({ "type": "player_gone" }));
+    assert!(PlayerEvent::Stopped.song().is_none());
+    assert!(PlayerEvent::PlayerGone.song().is_none());
+}
+
+#[test]
+fn server_message_envelope_is_tagged_by_kind() {
+    let song = serde_json::to_value(ServerMessage::Song(PlayerEvent::Stopped)).unwrap();
+    let config = serde_json::to_value(ServerMessage::Config(OverlayConfig::default())).unwrap();
+
+    assert_eq!(song["kind"], "song");
+    assert_eq!(song["payload"]["type"], "stopped");
+    assert_eq!(config["kind"], "config");
+    assert_eq!(config["payload"]["theme"], "frosted_glass");
+}
+
 #[test]
 fn platform_media_reader_implements_trait() {
     fn assert_media_reader<T: super::MediaReader>() {}
     assert_media_reader::<super::PlatformMediaReader>();
 }
 
+// CONTROL
+
+#[test]
+fn player_command_json_uses_action_tag() {
+    let seek: PlayerCommand =
+        serde_json::from_str(r#"{ "action": "seek_to", "position_secs": 42.5 }"#).unwrap();
+    let pause: PlayerCommand = serde_json::from_str(r#"{ "action": "play_pause" }"#).unwrap();
+
+    assert_eq!(
+        seek,
+        PlayerCommand::SeekTo {
+            position_secs: 42.5
+        }
+    );
+    assert_eq!(pause, PlayerCommand::PlayPause);
+    assert!(serde_json::from_str::<PlayerCommand>(r#"{ "action": "explode" }"#).is_err());
+}
+
+#[derive(Default)]
+struct RecordingController {
+    calls: RefCell<Vec<String>>,
+}
+
+impl MediaController for RecordingController {
+    fn play_pause(&self) -> Result<(), ControlError> {
+        self.calls.borrow_mut().push("play_pause".to_string());
+        Ok(())
+    }
+
+    fn next(&self) -> Result<(), ControlError> {
+        self.calls.borrow_mut().push("next".to_string());
+        Ok(())
+    }
+
+    fn previous(&self) -> Result<(), ControlError> {
+        Err(ControlError::NoPlayer)
+    }
+
+    fn seek_to(&self, position_secs: f64) -> Result<(), ControlError> {
+        self.calls
+            .borrow_mut()
+            .push(format!("seek_to {position_secs}"));
+        Ok(())
+    }
+
+    fn set_volume(&self, _volume: f64) -> Result<(), ControlError> {
+        Err(ControlError::Unsupported)
+    }
+}
+
+#[test]
+fn execute_dispatches_to_controller_methods() {
+    let controller = RecordingController::default();
+
+    controller.execute(&PlayerCommand::PlayPause).unwrap();
+    controller.execute(&PlayerCommand::Next).unwrap();
+    controller
+        .execute(&PlayerCommand::SeekTo { position_secs: 7.0 })
+        .unwrap();
+
+    assert_eq!(
+        *controller.calls.borrow(),
+        vec!["play_pause", "next", "seek_to 7"]
+    );
+    assert_eq!(
+        controller.execute(&PlayerCommand::Previous),
+        Err(ControlError::NoPlayer)
+    );
+    assert_eq!(
+        controller.execute(&PlayerCommand::SetVolume { volume: 0.5 }),
+        Err(ControlError::Unsupported)
+    );
+}
+
+// POLLING
+
+#[test]
+fn idle_polling_slows_down_only_while_watching() {
+    let tick = std::time::Duration::from_secs(1);
+
+    assert_eq!(super::poll_delay(tick, true, false), tick * super::IDLE_POLL_FACTOR);
+    assert_eq!(super::poll_delay(tick, true, true), tick);
+    // watcher gone, back to the plain interval
+    assert_eq!(super::poll_delay(tick, false, false), tick);
+}
+
 // LINUX
 
 
 #[cfg(target_os = "linux")]
 mod linux_tests {
     use super::super::linux::LinuxMediaReader;
-    use super::super::MediaReader;
+    use super::super::{MediaReader, PlayerSelection};
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::mpsc::{self, Receiver};
+    use std::time::Duration;
 
     #[test]
     fn linux_reader_does_not_panic_on_creation() {
@@ -104,7 +251,7 @@ mod linux_tests {
     fn linux_reader_poll_does_not_panic() {
         let result = std::panic::catch_unwind(|| {
             let reader = LinuxMediaReader::new();
-            let _ = reader.get_current_song();
+            let _ = reader.poll();
         });
 
         assert!(result.is_ok());
@@ -115,9 +262,153 @@ mod linux_tests {
         let reader = LinuxMediaReader::new();
 
         for _ in 0..10 {
-            let _ = reader.get_current_song();
+            let _ = reader.poll();
+        }
+    }
+
+    #[test]
+    fn linux_seek_positions_fit_mpris_microseconds() {
+        use super::super::linux::mpris_position;
+        use super::super::MAX_SEEK_SECS;
+
+        let max = mpris_position(MAX_SEEK_SECS).unwrap();
+        assert!(i64::try_from(max.as_micros()).is_ok());
+        assert_eq!(mpris_position(42.5).unwrap(), Duration::from_millis(42_500));
+        for bad in [1e14, 1e20, f64::INFINITY, f64::NAN, -1.0] {
+            assert!(mpris_position(bad).is_err(), "{bad}");
         }
     }
+
+    fn emit_properties_changed(interface: &str) {
+        let conn = dbus::blocking::Connection::new_session().unwrap();
+        let msg = dbus::Message::new_signal(
+            "/org/mpris/MediaPlayer2",
+            "org.freedesktop.DBus.Properties",
+            "PropertiesChanged",
+        )
+        .unwrap()
+        .append3(interface, dbus::arg::PropMap::new(), Vec::<String>::new());
+        conn.channel().send(msg).unwrap();
+        conn.channel().flush();
+    }
+
+    fn watch(reader: &LinuxMediaReader) -> Receiver<()> {
+        let (tx, rx) = mpsc::channel();
+        let watching = Arc::new(AtomicBool::new(false));
+        reader.watch_changes(
+            Arc::new(move || {
+                let _ = tx.send(());
+            }),
+            watching.clone(),
+        );
+
+        assert!(watching.load(Ordering::Relaxed));
+        rx
+    }
+
+    // answers the property reads mpris makes, playing "Song" at `position_secs`
+    struct FakePlayer(Arc<AtomicBool>);
+
+    impl FakePlayer {
+        fn start(name: &str, position_secs: i64) -> Self {
+            use dbus::arg::{PropMap, RefArg, Variant};
+            use dbus::channel::{MatchingReceiver, Sender};
+
+            let bus_name = format!("org.mpris.MediaPlayer2.{name}");
+            let stop = Arc::new(AtomicBool::new(false));
+            let (ready_tx, ready_rx) = mpsc::channel();
+            let stopped = stop.clone();
+            std::thread::spawn(move || {
+                let conn = dbus::blocking::Connection::new_session().unwrap();
+                conn.request_name(bus_name.as_str(), false, true, true)
+                    .unwrap();
+                conn.start_receive(
+                    dbus::message::MatchRule::new_method_call(),
+                    Box::new(move |msg, conn| {
+                        let property = match msg.member().as_deref() {
+                            Some("Get") => msg.read2::<&str, &str>().ok().map(|(_, p)| p),
+                            _ => None,
+                        };
+                        let reply = match property {
+                            Some("Identity") => msg.method_return().append1(Variant("Fake")),
+                            Some("PlaybackStatus") => {
+                                msg.method_return().append1(Variant("Playing"))
+                            }
+                            Some("Metadata") => {
+                                let mut metadata = PropMap::new();
+                                let title: Box<dyn RefArg> = Box::new("Song".to_string());
+                                let length: Box<dyn RefArg> = Box::new(300_000_000i64);
+                                metadata.insert("xesam:title".to_string(), Variant(title));
+                                metadata.insert("mpris:length".to_string(), Variant(length));
+                                msg.method_return().append1(Variant(metadata))
+                            }
+                            Some("Position") => msg
+                                .method_return()
+                                .append1(Variant(position_secs * 1_000_000)),
+                            _ => msg.error(
+                                &"org.freedesktop.DBus.Error.UnknownProperty".into(),
+                                c"not faked",
+                            ),
+                        };
+                        let _ = conn.send(reply);
+                        true
+                    }),
+                );
+                ready_tx.send(()).unwrap();
+                while !stopped.load(Ordering::Relaxed) {
+                    conn.process(Duration::from_millis(50)).unwrap();
+                }
+            });
+            ready_rx.recv().unwrap();
+            FakePlayer(stop)
+        }
+    }
+
+    impl Drop for FakePlayer {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn linux_reader_poll_once_takes_the_reported_position() {
+        let _player = FakePlayer::start("fake_position", 120);
+        let selection = PlayerSelection {
+            preferred: Some("*fake_position*".to_string()),
+            ..PlayerSelection::default()
+        };
+
+        let reader = LinuxMediaReader::try_new().unwrap();
+        reader.set_player_selection(selection.clone());
+        let song = reader.poll_once().song().cloned().unwrap();
+        assert_eq!(song.title, "Song");
+        assert_eq!(song.position_secs, 120);
+
+        // a running reader still starts a newly seen song from the top
+        let reader = LinuxMediaReader::try_new().unwrap();
+        reader.set_player_selection(selection);
+        assert!(reader.poll().song().unwrap().position_secs <= 1);
+    }
+
+    #[test]
+    fn linux_reader_wakes_on_player_properties_changed() {
+        let reader = LinuxMediaReader::new();
+        let rx = watch(&reader);
+
+        emit_properties_changed("org.mpris.MediaPlayer2.Player");
+
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+
+    #[test]
+    fn linux_reader_ignores_unrelated_properties_changed() {
+        let reader = LinuxMediaReader::new();
+        let rx = watch(&reader);
+
+        emit_properties_changed("org.mpris.MediaPlayer2.TrackList");
+
+        assert!(rx.recv_timeout(Duration::from_millis(300)).is_err());
+    }
 }
 
 
@@ -138,7 +429,7 @@ mod windows_tests {
     fn windows_reader_poll_does_not_panic() {
         let result = std::panic::catch_unwind(|| {
             let reader = WindowsMediaReader::new();
-            let _ = reader.get_current_song();
+            let _ = reader.poll();
         });
 
         assert!(result.is_ok());
@@ -149,7 +440,260 @@ mod windows_tests {
         let reader = WindowsMediaReader::new();
 
         for _ in 0..10 {
-            let _ = reader.get_current_song();
+            let _ = reader.poll();
+        }
+    }
+}
+
+// SELECTION
+
+mod selection_tests {
+    use super::super::PlayerSelection;
+    use crate::models::{OverlayConfig, PlaybackState, PlayerSummary};
+
+    fn player(identity: &str, bus_name: &str, status: PlaybackState) -> PlayerSummary {
+        PlayerSummary {
+            identity: identity.to_string(),
+            bus_name: format!("org.mpris.MediaPlayer2.{bus_name}"),
+            status,
+            followed: false,
         }
     }
-}
\ No newline at end of file
+
+    fn players() -> Vec<PlayerSummary> {
+        vec![
+            player(
+                "Mozilla Firefox",
+                "firefox.instance_1_42",
+                PlaybackState::Playing,
+            ),
+            player("Spotify", "spotify", PlaybackState::Paused),
+            player("mpv Media Player", "mpv", PlaybackState::Playing),
+        ]
+    }
+
+    #[test]
+    fn default_follows_first_playing_player() {
+        assert_eq!(PlayerSelection::default().pick(&players()), Some(0));
+        assert_eq!(PlayerSelection::default().pick(&[]), None);
+    }
+
+    #[test]
+    fn ignored_players_are_skipped_by_identity_or_bus_name() {
+        let selection = PlayerSelection {
+            ignored: vec!["*firefox*".to_string()],
+            ..PlayerSelection::default()
+        };
+        assert_eq!(selection.pick(&players()), Some(2));
+
+        let selection = PlayerSelection {
+            ignored: vec!["MOZILLA FIREFOX".to_string(), "org.mpris.*.mpv".to_string()],
+            ..PlayerSelection::default()
+        };
+        assert_eq!(selection.pick(&players()), Some(1));
+    }
+
+    #[test]
+    fn preferred_player_wins_even_when_paused() {
+        let selection = PlayerSelection {
+            preferred: Some("spotify".to_string()),
+            ..PlayerSelection::default()
+        };
+        assert_eq!(selection.pick(&players()), Some(1));
+    }
+
+    #[test]
+    fn ignore_list_beats_preference() {
+        let selection = PlayerSelection {
+            preferred: Some("spotify".to_string()),
+            ignored: vec!["spotify".to_string()],
+            ..PlayerSelection::default()
+        };
+        assert_eq!(selection.pick(&players()), Some(0));
+    }
+
+    #[test]
+    fn priority_breaks_ties_between_equally_playing_players() {
+        let selection = PlayerSelection {
+            priority: vec!["spotify".to_string(), "mpv*".to_string()],
+            ..PlayerSelection::default()
+        };
+        // spotify is paused, so the playing mpv wins over firefox
+        assert_eq!(selection.pick(&players()), Some(2));
+    }
+
+    #[test]
+    fn selection_is_built_from_config() {
+        let config = OverlayConfig {
+            preferred_player: Some(String::new()),
+            ignored_players: vec!["chromium".to_string()],
+            ..OverlayConfig::default()
+        };
+        let selection = PlayerSelection::from_config(&config);
+
+        assert_eq!(selection.preferred, None);
+        assert_eq!(selection.ignored, vec!["chromium"]);
+    }
+}
+
+// SCRIPTED
+mod scripted_tests {
+    use super::super::{
+        MediaController, MediaReader, PlayerSelection, ScriptedMediaReader, Timeline,
+    };
+    use crate::models::PlayerEvent;
+    use std::path::Path;
+    use std::time::{Duration, Instant};
+
+    const SCRIPT: &str = r#"{"steps": [
+        {"at_ms": 1000, "action": "play", "track": {"title": "One", "artist": "A", "length_secs": 60, "start_secs": 10}},
+        {"at_ms": 5000, "action": "pause"},
+        {"at_ms": 7000, "action": "resume"},
+        {"at_ms": 8000, "action": "seek", "position_secs": 50},
+        {"at_ms": 20000, "action": "stop"},
+        {"at_ms": 21000, "action": "gone"}
+    ]}"#;
+
+    fn reader(script: &str) -> (ScriptedMediaReader, Instant) {
+        let reader =
+            ScriptedMediaReader::from_timeline(Timeline::parse(script, Path::new(".")).unwrap());
+        (reader, Instant::now())
+    }
+
+    fn at(start: Instant, ms: u64) -> Instant {
+        start + Duration::from_millis(ms)
+    }
+
+    fn playing(event: &PlayerEvent) -> (u64, bool) {
+        let song = event.song().expect("a track");
+        (song.position_secs, song.is_playing)
+    }
+
+    #[test]
+    fn timeline_plays_pauses_seeks_and_stops() {
+        let (reader, start) = reader(SCRIPT);
+
+        assert_eq!(reader.poll_at(at(start, 500)), PlayerEvent::PlayerGone);
+        let event = reader.poll_at(at(start, 1100));
+        assert_eq!(event.song().unwrap().title, "One");
+        assert_eq!(playing(&event), (10, true));
+        assert_eq!(playing(&reader.poll_at(at(start, 4100))), (13, true));
+        // paused at 14s
+        assert_eq!(playing(&reader.poll_at(at(start, 6500))), (14, false));
+        assert_eq!(playing(&reader.poll_at(at(start, 7600))), (14, true));
+        assert_eq!(playing(&reader.poll_at(at(start, 9100))), (51, true));
+        // clamped to the track length
+        assert_eq!(playing(&reader.poll_at(at(start, 19900))), (60, true));
+        assert_eq!(reader.poll_at(at(start, 20100)), PlayerEvent::Stopped);
+        assert_eq!(reader.poll_at(at(start, 21100)), PlayerEvent::PlayerGone);
+    }
+
+    #[test]
+    fn skipped_polls_still_apply_every_step() {
+        let (reader, start) = reader(SCRIPT);
+
+        // pause and resume happened in between, 4s of the 8.5s were paused
+        assert_eq!(playing(&reader.poll_at(at(start, 7600))), (14, true));
+    }
+
+    #[test]
+    fn looping_timeline_starts_over() {
+        let (reader, start) = reader(
+            r#"{"loop_after_ms": 3000, "steps": [
+                {"at_ms": 0, "action": "play", "track": {"title": "Loop", "length_secs": 100}},
+                {"at_ms": 2000, "action": "stop"}
+            ]}"#,
+        );
+
+        assert_eq!(reader.poll_at(at(start, 2500)), PlayerEvent::Stopped);
+        assert_eq!(playing(&reader.poll_at(at(start, 4100))), (1, true));
+        assert_eq!(reader.poll_at(at(start, 11500)), PlayerEvent::Stopped);
+    }
+
+    #[test]
+    fn steps_are_sorted_by_time() {
+        let (reader, start) = reader(
+            r#"{"steps": [
+                {"at_ms": 2000, "action": "stop"},
+                {"at_ms": 0, "action": "play", "track": {"title": "First"}}
+            ]}"#,
+        );
+
+        assert_eq!(
+            reader.poll_at(at(start, 1000)).song().unwrap().title,
+            "First"
+        );
+        assert_eq!(reader.poll_at(at(start, 2100)), PlayerEvent::Stopped);
+    }
+
+    #[test]
+    fn art_is_read_relative_to_the_script() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("cover.png"), b"png").unwrap();
+        let script = dir.path().join("script.json");
+        std::fs::write(
+            &script,
+            r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Art", "art": "cover.png"}}]}"#,
+        )
+        .unwrap();
+
+        let reader = ScriptedMediaReader::from_timeline(Timeline::load(&script).unwrap());
+        let event = reader.poll();
+        assert_eq!(
+            event.song().unwrap().album_art.as_deref(),
+            Some(&b"png".to_vec())
+        );
+
+        std::fs::remove_file(dir.path().join("cover.png")).unwrap();
+        assert!(Timeline::load(&script).is_err());
+    }
+
+    #[test]
+    fn invalid_script_is_an_error() {
+        assert!(
+            Timeline::parse(
+                r#"{"steps": [{"at_ms": 0, "action": "rewind"}]}"#,
+                Path::new(".")
+            )
+            .is_err()
+        );
+        assert!(Timeline::parse("[]", Path::new(".")).is_err());
+    }
+
+    #[test]
+    fn demo_timeline_parses_and_plays() {
+        let reader = ScriptedMediaReader::new();
+        assert!(reader.poll().song().is_some());
+        assert_eq!(reader.list_players().len(), 1);
+    }
+
+    #[test]
+    fn controls_act_on_the_scripted_player() {
+        let (reader, _) = reader(
+            r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "One", "length_secs": 300}}]}"#,
+        );
+        assert!(reader.poll().song().unwrap().is_playing);
+
+        reader.play_pause().unwrap();
+        assert!(!reader.poll().song().unwrap().is_playing);
+        reader.seek_to(120.0).unwrap();
+        assert_eq!(playing(&reader.poll()), (120, false));
+        reader.play_pause().unwrap();
+        assert!(reader.poll().song().unwrap().is_playing);
+        assert!(reader.next().is_err());
+    }
+
+    #[test]
+    fn ignored_scripted_player_is_gone() {
+        let (reader, _) =
+            reader(r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "One"}}]}"#);
+        reader.set_player_selection(PlayerSelection {
+            ignored: vec!["scripted".to_string()],
+            ..PlayerSelection::default()
+        });
+
+        assert_eq!(reader.poll(), PlayerEvent::PlayerGone);
+        assert!(!reader.list_players()[0].followed);
+        assert!(reader.play_pause().is_err());
+    }
+}
@@ -1,10 +1,9 @@
-use crate::media_reader::MediaReader;
-use crate::models::SongInfo;
-use base64::{Engine as _, engine::general_purpose};
+use crate::media_reader::{ControlError, MediaController, MediaReader, PlayerSelection};
+use crate::models::{PlaybackState, PlayerEvent, PlayerSummary, SongInfo};
 use std::cell::RefCell;
 use std::sync::Arc;
 use windows::Media::Control::{
-    GlobalSystemMediaTransportControlsSessionManager,
+    GlobalSystemMediaTransportControlsSession, GlobalSystemMediaTransportControlsSessionManager,
     GlobalSystemMediaTransportControlsSessionMediaProperties,
     GlobalSystemMediaTransportControlsSessionPlaybackStatus,
 };
@@ -12,8 +11,10 @@ use windows::Storage::Streams::DataReader;
 
 pub struct WindowsMediaReader {
     manager: Option<GlobalSystemMediaTransportControlsSessionManager>,
+    selection: RefCell<PlayerSelection>,
+    players: RefCell<Vec<PlayerSummary>>,
     last_title: RefCell<Option<String>>,
-    last_art: RefCell<Option<Arc<String>>>,
+    last_art: RefCell<Option<Arc<Vec<u8>>>>,
 }
 
 impl MediaReader for WindowsMediaReader {
@@ -24,15 +25,47 @@ impl MediaReader for WindowsMediaReader {
 
         Self {
             manager,
+            selection: RefCell::new(PlayerSelection::default()),
+            players: RefCell::new(Vec::new()),
             last_title: RefCell::new(None),
             last_art: RefCell::new(None),
         }
     }
 
-    fn get_current_song(&self) -> Option<SongInfo> {
-        let manager = self.manager.as_ref()?;
-        let session = manager.GetCurrentSession().ok()?;
+    fn poll(&self) -> PlayerEvent {
+        let Ok(session) = self.current_session() else {
+            return PlayerEvent::PlayerGone;
+        };
+
+        let stopped = session
+            .GetPlaybackInfo()
+            .and_then(|info| info.PlaybackStatus())
+            .map(|s| {
+                s == GlobalSystemMediaTransportControlsSessionPlaybackStatus::Stopped
+                    || s == GlobalSystemMediaTransportControlsSessionPlaybackStatus::Closed
+            })
+            .unwrap_or(false);
+        if stopped {
+            return PlayerEvent::Stopped;
+        }
+
+        match self.read_song(&session) {
+            Some(info) => PlayerEvent::TrackChanged(info),
+            None => PlayerEvent::Stopped,
+        }
+    }
 
+    fn set_player_selection(&self, selection: PlayerSelection) {
+        *self.selection.borrow_mut() = selection;
+    }
+
+    fn list_players(&self) -> Vec<PlayerSummary> {
+        self.players.borrow().clone()
+    }
+}
+
+impl WindowsMediaReader {
+    fn read_song(&self, session: &GlobalSystemMediaTransportControlsSession) -> Option<SongInfo> {
         let media_props = session.TryGetMediaPropertiesAsync().ok()?.get().ok()?;
 
         let title = media_props
@@ -77,17 +110,17 @@ impl MediaReader for WindowsMediaReader {
         let identity = format!("{}|{}", source_app, title);
 
         if last_title_ref.as_deref() != Some(identity.as_str()) {
-            *last_art_ref = get_thumbnail_base64(&media_props).map(Arc::new);
+            *last_art_ref = get_thumbnail(&media_props).map(Arc::new);
             *last_title_ref = Some(identity);
         }
 
-        let album_art_base64 = last_art_ref.clone();
+        let album_art = last_art_ref.clone();
         // eprintln!("{} - {} [{}]", artist, title, source_app);
         Some(SongInfo {
             title,
             artist,
             album,
-            album_art_base64,
+            album_art,
             position_secs: if length_secs > 0 {
                 position_secs.min(length_secs)
             } else {
@@ -95,13 +128,114 @@ impl MediaReader for WindowsMediaReader {
             },
             length_secs,
             is_playing,
+            ..SongInfo::default()
         })
     }
 }
 
-fn get_thumbnail_base64(
+impl MediaController for WindowsMediaReader {
+    fn play_pause(&self) -> Result<(), ControlError> {
+        let session = self.current_session()?;
+        checked(session.TryTogglePlayPauseAsync().and_then(|op| op.get()))
+    }
+
+    fn next(&self) -> Result<(), ControlError> {
+        let session = self.current_session()?;
+        checked(session.TrySkipNextAsync().and_then(|op| op.get()))
+    }
+
+    fn previous(&self) -> Result<(), ControlError> {
+        let session = self.current_session()?;
+        checked(session.TrySkipPreviousAsync().and_then(|op| op.get()))
+    }
+
+    fn seek_to(&self, position_secs: f64) -> Result<(), ControlError> {
+        let session = self.current_session()?;
+        // 100ns ticks
+        let ticks = (position_secs.max(0.0) * 10_000_000.0) as i64;
+        checked(
+            session
+                .TryChangePlaybackPositionAsync(ticks)
+                .and_then(|op| op.get()),
+        )
+    }
+
+    fn set_volume(&self, _volume: f64) -> Result<(), ControlError> {
+        // GSMTC has no per session volume
+        Err(ControlError::Unsupported)
+    }
+}
+
+impl WindowsMediaReader {
+    fn current_session(&self) -> Result<GlobalSystemMediaTransportControlsSession, ControlError> {
+        self.select_session().ok_or(ControlError::NoPlayer)
+    }
+
+    // picks the session to follow and remembers every session seen for list_players
+    fn select_session(&self) -> Option<GlobalSystemMediaTransportControlsSession> {
+        let mut sessions: Vec<GlobalSystemMediaTransportControlsSession> = self
+            .manager
+            .as_ref()
+            .and_then(|manager| manager.GetSessions().ok())
+            .map(|list| {
+                (0..list.Size().unwrap_or(0))
+                    .filter_map(|i| list.GetAt(i).ok())
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut summaries: Vec<PlayerSummary> = sessions
+            .iter()
+            .map(|session| {
+                let app_id = session
+                    .SourceAppUserModelId()
+                    .map(|s| s.to_string())
+                    .unwrap_or_default();
+                let status = session
+                    .GetPlaybackInfo()
+                    .and_then(|info| info.PlaybackStatus())
+                    .map(|s| match s {
+                        GlobalSystemMediaTransportControlsSessionPlaybackStatus::Playing => {
+                            PlaybackState::Playing
+                        }
+                        GlobalSystemMediaTransportControlsSessionPlaybackStatus::Paused => {
+                            PlaybackState::Paused
+                        }
+                        _ => PlaybackState::Stopped,
+                    })
+                    .unwrap_or(PlaybackState::Stopped);
+
+                PlayerSummary {
+                    identity: app_id.clone(),
+                    bus_name: app_id,
+                    status,
+                    followed: false,
+                }
+            })
+            .collect();
+
+        let picked = self.selection.borrow().pick(&summaries);
+        if let Some(index) = picked {
+            summaries[index].followed = true;
+        }
+        *self.players.borrow_mut() = summaries;
+
+        picked.map(|index| sessions.swap_remove(index))
+    }
+}
+
+// the Try* calls resolve to false when the session refuses
+fn checked(result: windows::core::Result<bool>) -> Result<(), ControlError> {
+    match result {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(ControlError::Unsupported),
+        Err(e) => Err(ControlError::Failed(e.to_string())),
+    }
+}
+
+fn get_thumbnail(
     media_props: &GlobalSystemMediaTransportControlsSessionMediaProperties,
-) -> Option<String> {
+) -> Option<Vec<u8>> {
     let thumb_ref = media_props.Thumbnail().ok()?;
     let stream = thumb_ref.OpenReadAsync().ok()?.get().ok()?;
     let size = stream.Size().ok()? as u32;
@@ -112,5 +246,5 @@ fn get_thumbnail_base64(
     reader.LoadAsync(size).ok()?.get().ok()?;
     let mut buf = vec![0u8; size as usize];
     reader.ReadBytes(&mut buf).ok()?;
-    Some(general_purpose::STANDARD.encode(&buf))
+    Some(buf)
 }
@@ -1,3 +1,4 @@
+use crate::file_output::FileOutputSettings;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 
@@ -6,14 +7,111 @@ pub struct SongInfo {
     pub title: String,
     pub artist: String,
     pub album: String,
+    // /api/art/<content hash>, scaled down to art_thumbnail_px
+    pub album_art_url: Option<String>,
+    // same cover at art_background_px, for the blurred ambient background
+    pub album_art_background_url: Option<String>,
+    // of what the urls serve, e.g. "image/jpeg"
+    pub album_art_mime: Option<String>,
+    pub album_art_palette: Option<ArtPalette>,
+    // only filled with `inline_album_art` on, for overlays predating album_art_url
     pub album_art_base64: Option<Arc<String>>,
+    // raw image from the reader, published through the art store
+    #[serde(skip)]
+    pub album_art: Option<Arc<Vec<u8>>>,
+    // where the player says the art is, http(s) ones are fetched when album_art is None
+    #[serde(skip)]
+    pub album_art_source: Option<String>,
     pub position_secs: u64,
     pub length_secs: u64,
     pub is_playing: bool,
 }
 
+// picked from the cover, "#rrggbb" each
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct ArtPalette {
+    pub dominant: String,
+    pub vibrant: String,
+    pub muted: String,
+    // readable on dominant
+    pub text: String,
+}
+
+// what the reader saw on its last poll, sent over /ws as
+// {"type": "track_changed", "song": {...}} / {"type": "stopped"}
+// one per poll, not worth boxing the song
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", content = "song", rename_all = "snake_case")]
+pub enum PlayerEvent {
+    TrackChanged(SongInfo),
+    // player still around but nothing loaded / playback stopped
+    Stopped,
+    // no player on the bus / no media session
+    PlayerGone,
+}
+
+// envelope for everything pushed over /ws, e.g.
+// {"kind": "song", "payload": {"type": "stopped"}}
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "payload", rename_all = "snake_case")]
+pub enum ServerMessage {
+    Song(PlayerEvent),
+    Config(OverlayConfig),
+    // whenever a player appears, goes away or changes status
+    Players(Vec<PlayerSummary>),
+}
+
+// sorted by how likely the player is the one being listened to
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum PlaybackState {
+    Playing,
+    Paused,
+    Stopped,
+}
+
+// GET /api/players
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayerSummary {
+    pub identity: String,
+    // MPRIS bus name on linux, app user model id on windows
+    pub bus_name: String,
+    pub status: PlaybackState,
+    // the one the overlay is showing
+    pub followed: bool,
+}
+
+// client -> server, over /ws or as POST /api/player/* e.g.
+// {"action": "seek_to", "position_secs": 42.0}
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum PlayerCommand {
+    PlayPause,
+    Next,
+    Previous,
+    SeekTo { position_secs: f64 },
+    // 0.0 - 1.0
+    SetVolume { volume: f64 },
+}
+
+impl PlayerEvent {
+    pub fn song(&self) -> Option<&SongInfo> {
+        match self {
+            PlayerEvent::TrackChanged(info) => Some(info),
+            PlayerEvent::Stopped | PlayerEvent::PlayerGone => None,
+        }
+    }
+}
+
+// bump together with a new step in config::MIGRATIONS
+pub const CONFIG_VERSION: u32 = 1;
+
+// missing fields fall back to Default so older files keep loading
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct OverlayConfig {
+    pub version: u32,
     pub theme: String,
     pub show_thumbnail: bool,
     pub show_artist: bool,
@@ -33,6 +131,22 @@ pub struct OverlayConfig {
     pub custom_css: String,
 
     pub transition_animation: String,
+
+    // matched against player identity ("Spotify") or bus name, `*` wildcards allowed
+    pub preferred_player: Option<String>,
+    pub ignored_players: Vec<String>,
+    pub player_priority: Vec<String>,
+
+    // also send the cover as base64 in every song message, like before /api/art
+    pub inline_album_art: bool,
+    // longest side of the served art, px
+    pub art_thumbnail_px: u32,
+    pub art_background_px: u32,
+    // color the overlay from album_art_palette instead of the fixed colors, when there is one
+    pub use_art_colors: bool,
+
+    // text and image files for obs, picked up without a restart
+    pub file_output: FileOutputSettings,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -47,6 +161,7 @@ pub enum OverlayPosition {
 impl Default for OverlayConfig {
     fn default() -> Self {
         Self {
+            version: CONFIG_VERSION,
             theme: "frosted_glass".to_string(),
             show_thumbnail: true,
             show_artist: true,
@@ -62,6 +177,14 @@ impl Default for OverlayConfig {
             blur_px: 18,
             custom_css: String::new(),
             transition_animation: "slide_up".to_string(),
+            preferred_player: None,
+            ignored_players: Vec::new(),
+            player_priority: Vec::new(),
+            inline_album_art: false,
+            art_thumbnail_px: 300,
+            art_background_px: 64,
+            use_art_colors: false,
+            file_output: FileOutputSettings::default(),
         }
     }
 }
@@ -1,81 +1,183 @@
-use crate::config::ConfigManager;
-use crate::models::{OverlayConfig, SongInfo};
+use crate::art::{self, ArtFetcher, ArtStore};
+use crate::assets::StaticFiles;
+use crate::auth::Auth;
+use crate::config::{self, ConfigManager, ConfigStatus};
+use crate::events::{EventLog, Subscription};
+use crate::history::{History, HistoryEntry};
+use crate::media_reader::{ControlError, ControlRequest, MAX_SEEK_SECS, ReaderMessage};
+use crate::models::{
+    OverlayConfig, PlayerCommand, PlayerEvent, PlayerSummary, ServerMessage, SongInfo,
+};
+use crate::template::Template;
 use axum::{
     Json, Router,
     extract::{
-        State,
+        FromRequestParts, Path, Query, State,
         ws::{Message, WebSocket, WebSocketUpgrade},
     },
-    http::StatusCode,
-    response::{IntoResponse, Response},
-    routing::{get, get_service},
+    http::{HeaderMap, StatusCode, header, request::Parts},
+    response::{
+        IntoResponse, Response,
+        sse::{self, KeepAlive, Sse},
+    },
+    routing::{get, post},
 };
-use futures::{sink::SinkExt, stream::StreamExt};
-use std::net::SocketAddr;
-use std::sync::{Arc, Mutex};
-use tokio::sync::broadcast;
-use tower_http::cors::CorsLayer;
-use tower_http::services::{ServeDir, ServeFile};
+use futures::{
+    sink::SinkExt,
+    stream::{self, Stream, StreamExt},
+};
+use serde::Deserialize;
+use std::convert::Infallible;
+use std::sync::{Arc, Mutex, mpsc};
+use tokio::sync::{broadcast, oneshot};
 
 pub struct AppState {
     pub config_manager: ConfigManager,
+    pub art: ArtStore,
+    pub art_fetcher: ArtFetcher,
+    pub events: EventLog,
+    pub auth: Auth,
+    pub static_files: StaticFiles,
+    pub history: History,
     pub song_info: Arc<Mutex<Option<SongInfo>>>,
-    pub tx: broadcast::Sender<SongInfo>,
+    // as of the last poll
+    pub players: Arc<Mutex<Vec<PlayerSummary>>>,
+    pub tx: broadcast::Sender<ServerMessage>,
+    pub reader_tx: mpsc::Sender<ReaderMessage>,
 }
 
-pub async fn run_server(state: Arc<AppState>, shutdown_rx: tokio::sync::oneshot::Receiver<()>) {
-    let app = Router::new()
+pub fn router(state: Arc<AppState>) -> Router {
+    let cors = state.auth.cors();
+    Router::new()
         .route("/ws", get(ws_handler))
+        .route("/api/events", get(sse_handler))
         .route("/api/config", get(get_config).post(update_config))
-        .route("/", get_service(ServeFile::new("static/overlay.html")))
-        .route(
-            "/customize",
-            get_service(ServeFile::new("static/customize.html")),
-        )
-        .fallback_service(ServeDir::new("static"))
-        .layer(CorsLayer::permissive())
-        .with_state(state);
+        .route("/api/config/status", get(get_config_status))
+        .route("/api/players", get(get_players))
+        .route("/api/art/:hash", get(get_art))
+        .route("/api/history", get(get_history))
+        .route("/api/now-playing", get(get_now_playing))
+        .route("/api/now-playing.txt", get(get_now_playing_text))
+        .route("/api/player/play_pause", post(player_play_pause))
+        .route("/api/player/next", post(player_next))
+        .route("/api/player/previous", post(player_previous))
+        .route("/api/player/seek", post(player_seek))
+        .route("/api/player/volume", post(player_volume))
+        .fallback(get(serve_static))
+        .layer(cors)
+        .with_state(state)
+}
 
-    let addr = SocketAddr::from(([127, 0, 0, 1], 3333));
+pub async fn run_server(
+    state: Arc<AppState>,
+    addr: &str,
+    shutdown_rx: tokio::sync::oneshot::Receiver<()>,
+) -> std::io::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await.map_err(|e| {
+        std::io::Error::new(
+            e.kind(),
+            format!("could not listen on {addr}: {e} (pick another one with --host/--port)"),
+        )
+    })?;
 
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    
-    axum::serve(listener, app)
+    axum::serve(listener, router(state))
         .with_graceful_shutdown(async {
             let _ = shutdown_rx.await;
         })
         .await
-        .unwrap();
 }
 
-async fn ws_handler(ws: WebSocketUpgrade, State(state): State<Arc<AppState>>) -> Response {
-    ws.on_upgrade(|socket| handle_socket(socket, state))
+#[derive(Deserialize)]
+struct TokenQuery {
+    token: Option<String>,
+}
+
+// the token as "Authorization: Bearer <token>" or ?token=<token>
+pub struct Authorized;
+
+#[axum::async_trait]
+impl FromRequestParts<Arc<AppState>> for Authorized {
+    type Rejection = Response;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &Arc<AppState>,
+    ) -> Result<Self, Self::Rejection> {
+        let from_header = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::to_string);
+        let token = from_header.or_else(|| {
+            Query::<TokenQuery>::try_from_uri(&parts.uri)
+                .ok()
+                .and_then(|query| query.0.token)
+        });
+        match token {
+            Some(token) if state.auth.check(token.trim()) => Ok(Authorized),
+            _ => Err(error_response(
+                StatusCode::UNAUTHORIZED,
+                "missing or wrong token",
+            )),
+        }
+    }
+}
+
+// anyone can watch, only sockets opened with the token can control the player
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<Arc<AppState>>,
+    authorized: Option<Authorized>,
+) -> Response {
+    let can_control = authorized.is_some();
+    ws.on_upgrade(move |socket| handle_socket(socket, state, can_control))
 }
 
-async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
+async fn handle_socket(socket: WebSocket, state: Arc<AppState>, can_control: bool) {
     let (mut sender, mut receiver) = socket.split();
     let mut rx = state.tx.subscribe();
 
-    // init state
-    let initial_info = state.song_info.lock().unwrap().clone();
-    if let Some(info) = initial_info
-        && let Ok(msg) = serde_json::to_string(&info)
-    {
-        let _ = sender.send(Message::Text(msg)).await;
+    // init state, so a reconnecting overlay drops a track that ended meanwhile
+    // and picks up config changes it missed
+    let initial_event = match state.song_info.lock().unwrap().clone() {
+        Some(info) => PlayerEvent::TrackChanged(info),
+        None => PlayerEvent::Stopped,
+    };
+    for message in [
+        ServerMessage::Config(state.config_manager.get_config()),
+        ServerMessage::Song(initial_event),
+    ] {
+        if let Ok(msg) = serde_json::to_string(&message) {
+            let _ = sender.send(Message::Text(msg)).await;
+        }
     }
 
     let mut send_task = tokio::spawn(async move {
-        while let Ok(info) = rx.recv().await {
-            if let Ok(msg) = serde_json::to_string(&info)
+        while let Ok(message) = rx.recv().await {
+            if let Ok(msg) = serde_json::to_string(&message)
                 && sender.send(Message::Text(msg)).await.is_err()
             {
                 break;
             }
         }
     });
-    // keep alive
-    let mut recv_task =
-        tokio::spawn(async move { while let Some(Ok(_)) = receiver.next().await {} });
+    // control messages, anything else just keeps the socket alive
+    let reader_tx = state.reader_tx.clone();
+    let mut recv_task = tokio::spawn(async move {
+        while let Some(Ok(msg)) = receiver.next().await {
+            if let Message::Text(text) = msg
+                && can_control
+                && let Ok(command) = serde_json::from_str::<PlayerCommand>(&text)
+                && let Ok(command) = prepare_command(&state, command)
+            {
+                let _ = reader_tx.send(ReaderMessage::Control(ControlRequest {
+                    command,
+                    reply: None,
+                }));
+            }
+        }
+    });
 
     tokio::select! {
         _ = (&mut send_task) => recv_task.abort(),
@@ -83,16 +185,348 @@ async fn handle_socket(socket: WebSocket, state: Arc<AppState>) {
     };
 }
 
+// text/event-stream with the same messages as /ws, as "song", "config" and "players" events
+async fn sse_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> Sse<impl Stream<Item = Result<sse::Event, Infallible>>> {
+    let after = headers
+        .get("last-event-id")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| state.events.parse_id(value));
+    let Subscription {
+        last_id,
+        missed,
+        rx,
+    } = state.events.subscribe(after);
+
+    // a resuming client gets what it missed, anyone else the current state
+    let initial: Vec<sse::Event> = match missed {
+        Some(missed) => missed
+            .iter()
+            .map(|event| sse_event(&event.message, state.events.id(event.id)))
+            .collect(),
+        None => {
+            let song = match state.song_info.lock().unwrap().clone() {
+                Some(info) => PlayerEvent::TrackChanged(info),
+                None => PlayerEvent::Stopped,
+            };
+            [
+                ServerMessage::Config(state.config_manager.get_config()),
+                ServerMessage::Players(state.players.lock().unwrap().clone()),
+                ServerMessage::Song(song),
+            ]
+            .iter()
+            .map(|message| sse_event(message, state.events.id(last_id)))
+            .collect()
+        }
+    };
+    let live = stream::unfold((rx, state), |(mut rx, state)| async move {
+        match rx.recv().await {
+            Ok(event) => {
+                let id = state.events.id(event.id);
+                Some((sse_event(&event.message, id), (rx, state)))
+            }
+            // fell behind, closing makes the client reconnect with its last id
+            Err(_) => None,
+        }
+    });
+
+    Sse::new(stream::iter(initial).chain(live).map(Ok)).keep_alive(KeepAlive::default())
+}
+
+fn sse_event(message: &ServerMessage, id: String) -> sse::Event {
+    let event = sse::Event::default().id(id);
+    match message {
+        ServerMessage::Song(song) => event.event("song").json_data(song),
+        ServerMessage::Config(config) => event.event("config").json_data(config),
+        ServerMessage::Players(players) => event.event("players").json_data(players),
+    }
+    .expect("messages serialize")
+}
+
 async fn get_config(State(state): State<Arc<AppState>>) -> Json<OverlayConfig> {
     Json(state.config_manager.get_config())
 }
 
+async fn get_config_status(State(state): State<Arc<AppState>>) -> Json<ConfigStatus> {
+    Json(state.config_manager.status())
+}
+
 async fn update_config(
+    _: Authorized,
     State(state): State<Arc<AppState>>,
     Json(payload): Json<OverlayConfig>,
-) -> impl IntoResponse {
+) -> Response {
+    if let Err(errors) = config::validate(&payload) {
+        return (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(serde_json::json!({ "errors": errors })),
+        )
+            .into_response();
+    }
+
     match state.config_manager.update_config(payload) {
-        Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        Ok(_) => StatusCode::OK.into_response(),
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
     }
 }
+
+// NOW PLAYING
+
+// 204 when nothing plays, 304 when the client's etag still matches, so polling is cheap
+async fn get_now_playing(State(state): State<Arc<AppState>>, headers: HeaderMap) -> Response {
+    let Some(song) = state.song_info.lock().unwrap().clone() else {
+        return StatusCode::NO_CONTENT.into_response();
+    };
+    let body = serde_json::to_vec(&song).expect("song serializes");
+    let etag = format!("\"{}\"", &art::content_hash(&body)[..16]);
+
+    let headers_out = [
+        (header::ETAG, etag.clone()),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    if etag_matches(&headers, &etag) {
+        return (StatusCode::NOT_MODIFIED, headers_out).into_response();
+    }
+    (
+        headers_out,
+        [(header::CONTENT_TYPE, "application/json")],
+        body,
+    )
+        .into_response()
+}
+
+fn etag_matches(headers: &HeaderMap, etag: &str) -> bool {
+    headers
+        .get_all(header::IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim().trim_start_matches("W/"))
+        .any(|tag| tag == etag || tag == "*")
+}
+
+// plenty for a line of text, keeps parsing cheap for whoever asks
+const MAX_TEMPLATE_LEN: usize = 1024;
+
+#[derive(Deserialize)]
+struct TextQuery {
+    template: Option<String>,
+}
+
+// e.g. /api/now-playing.txt?template={artist} - {title}, empty when nothing plays
+async fn get_now_playing_text(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TextQuery>,
+) -> Response {
+    if query
+        .template
+        .as_ref()
+        .is_some_and(|t| t.len() > MAX_TEMPLATE_LEN)
+    {
+        return error_response(
+            StatusCode::BAD_REQUEST,
+            &format!("template is longer than {MAX_TEMPLATE_LEN} bytes"),
+        );
+    }
+    let template = match query.template.as_deref().map(Template::parse) {
+        Some(Ok(template)) => template,
+        Some(Err(e)) => return error_response(StatusCode::BAD_REQUEST, &e.to_string()),
+        None => Template::default(),
+    };
+    let text = template.render(state.song_info.lock().unwrap().as_ref());
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], text).into_response()
+}
+
+// STATIC
+
+// revalidated on every load, so a new build or a --static-dir edit shows up right away
+async fn serve_static(
+    State(state): State<Arc<AppState>>,
+    uri: axum::http::Uri,
+    headers: HeaderMap,
+) -> Response {
+    let Some(asset) = state.static_files.get(uri.path()) else {
+        return StatusCode::NOT_FOUND.into_response();
+    };
+    let headers_out = [
+        (header::ETAG, asset.etag.clone()),
+        (header::CACHE_CONTROL, "no-cache".to_string()),
+    ];
+    if etag_matches(&headers, &asset.etag) {
+        return (StatusCode::NOT_MODIFIED, headers_out).into_response();
+    }
+    (
+        headers_out,
+        [(header::CONTENT_TYPE, asset.content_type)],
+        asset.bytes.into_owned(),
+    )
+        .into_response()
+}
+
+// HISTORY
+
+const HISTORY_LIMIT: usize = 50;
+const HISTORY_MAX_LIMIT: usize = 1000;
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    // unix seconds, plays that ended before this are left out
+    since: Option<u64>,
+    limit: Option<usize>,
+}
+
+// newest first
+async fn get_history(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<HistoryQuery>,
+) -> Json<Vec<HistoryEntry>> {
+    let limit = query.limit.unwrap_or(HISTORY_LIMIT).min(HISTORY_MAX_LIMIT);
+    Json(state.history.query(query.since, limit))
+}
+
+// PLAYER CONTROL
+
+async fn get_players(State(state): State<Arc<AppState>>) -> Json<Vec<PlayerSummary>> {
+    Json(state.players.lock().unwrap().clone())
+}
+
+// the hash is the content, so clients can keep it forever
+async fn get_art(State(state): State<Arc<AppState>>, Path(hash): Path<String>) -> Response {
+    match state.art.get(&hash) {
+        Some(art) => (
+            [
+                (header::CONTENT_TYPE, art.content_type),
+                (header::CACHE_CONTROL, "public, max-age=31536000, immutable"),
+            ],
+            art.bytes.as_ref().clone(),
+        )
+            .into_response(),
+        None => error_response(StatusCode::NOT_FOUND, "unknown album art"),
+    }
+}
+
+#[derive(Deserialize)]
+struct SeekBody {
+    position_secs: f64,
+}
+
+#[derive(Deserialize)]
+struct VolumeBody {
+    volume: f64,
+}
+
+async fn player_play_pause(_: Authorized, State(state): State<Arc<AppState>>) -> Response {
+    run_command(&state, PlayerCommand::PlayPause).await
+}
+
+async fn player_next(_: Authorized, State(state): State<Arc<AppState>>) -> Response {
+    run_command(&state, PlayerCommand::Next).await
+}
+
+async fn player_previous(_: Authorized, State(state): State<Arc<AppState>>) -> Response {
+    run_command(&state, PlayerCommand::Previous).await
+}
+
+async fn player_seek(
+    _: Authorized,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<SeekBody>,
+) -> Response {
+    let command = PlayerCommand::SeekTo {
+        position_secs: body.position_secs,
+    };
+    run_command(&state, command).await
+}
+
+async fn player_volume(
+    _: Authorized,
+    State(state): State<Arc<AppState>>,
+    Json(body): Json<VolumeBody>,
+) -> Response {
+    let command = PlayerCommand::SetVolume {
+        volume: body.volume,
+    };
+    run_command(&state, command).await
+}
+
+fn validate_command(command: &PlayerCommand) -> Result<(), &'static str> {
+    match *command {
+        PlayerCommand::SeekTo { position_secs }
+            if !(0.0..MAX_SEEK_SECS).contains(&position_secs) =>
+        {
+            Err("position_secs must be a positive number of seconds")
+        }
+        PlayerCommand::SetVolume { volume } if !(0.0..=1.0).contains(&volume) => {
+            Err("volume must be between 0.0 and 1.0")
+        }
+        _ => Ok(()),
+    }
+}
+
+// validated, and seeks kept within the current track
+fn prepare_command(
+    state: &AppState,
+    command: PlayerCommand,
+) -> Result<PlayerCommand, &'static str> {
+    validate_command(&command)?;
+    let length_secs = state
+        .song_info
+        .lock()
+        .unwrap()
+        .as_ref()
+        .map_or(0, |song| song.length_secs);
+    Ok(match command {
+        PlayerCommand::SeekTo { position_secs } if length_secs > 0 => PlayerCommand::SeekTo {
+            position_secs: position_secs.min(length_secs as f64),
+        },
+        command => command,
+    })
+}
+
+async fn run_command(state: &AppState, command: PlayerCommand) -> Response {
+    let command = match prepare_command(state, command) {
+        Ok(command) => command,
+        Err(message) => return error_response(StatusCode::UNPROCESSABLE_ENTITY, message),
+    };
+
+    let (reply_tx, reply_rx) = oneshot::channel();
+    let request = ControlRequest {
+        command,
+        reply: Some(reply_tx),
+    };
+    if state
+        .reader_tx
+        .send(ReaderMessage::Control(request))
+        .is_err()
+    {
+        return error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "media reader is not running",
+        );
+    }
+
+    match reply_rx.await {
+        Ok(Ok(())) => StatusCode::NO_CONTENT.into_response(),
+        Ok(Err(e)) => {
+            let status = match e {
+                ControlError::NoPlayer => StatusCode::NOT_FOUND,
+                ControlError::Unsupported => StatusCode::NOT_IMPLEMENTED,
+                ControlError::Failed(_) => StatusCode::BAD_GATEWAY,
+            };
+            error_response(status, &e.to_string())
+        }
+        Err(_) => error_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "media reader is not running",
+        ),
+    }
+}
+
+fn error_response(status: StatusCode, message: &str) -> Response {
+    (status, Json(serde_json::json!({ "error": message }))).into_response()
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,960 @@
+use super::{AppState, router};
+use crate::art::{ArtFetcher, ArtStore, FetchLimits};
+use crate::assets::StaticFiles;
+use crate::auth::Auth;
+use crate::cli::ConfigCommand;
+use crate::commands;
+use crate::config::ConfigManager;
+use crate::events::EventLog;
+use crate::history::{History, HistoryEntry, HistorySettings};
+use crate::instance::{Acquired, InstanceInfo, InstanceLock};
+use crate::media_reader::{
+    self, ControlError, MediaController, MediaReader, PlayerSelection, ReaderMessage,
+    ScriptedMediaReader, Timeline,
+};
+use crate::models::{OverlayConfig, PlayerEvent, PlayerSummary, ServerMessage};
+use crate::settings;
+use axum::body::Body;
+use axum::http::{Request, StatusCode, header};
+use futures::{SinkExt, StreamExt};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio::sync::broadcast;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use tower::ServiceExt;
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+const TEST_POLL_INTERVAL: Duration = Duration::from_millis(20);
+const TOKEN: &str = "test-token";
+
+struct TestServer {
+    state: Arc<AppState>,
+    reader_rx: Option<mpsc::Receiver<ReaderMessage>>,
+    url: String,
+    _dir: tempfile::TempDir,
+}
+
+async fn start_server() -> TestServer {
+    let dir = tempfile::tempdir().unwrap();
+    let (tx, _rx) = broadcast::channel(100);
+    let (reader_tx, reader_rx) = mpsc::channel();
+    let state = Arc::new(AppState {
+        config_manager: ConfigManager::new(dir.path().join("config.json"), tx.clone()),
+        art: ArtStore::default(),
+        art_fetcher: ArtFetcher::new(None, FetchLimits::default()),
+        events: EventLog::new(&tx),
+        auth: Auth::new(TOKEN.to_string(), &["https://allowed.example".to_string()]),
+        static_files: StaticFiles::Embedded,
+        history: History::load(dir.path().join("history.jsonl"), HistorySettings::default()),
+        song_info: Arc::new(Mutex::new(None)),
+        players: Arc::new(Mutex::new(Vec::new())),
+        tx,
+        reader_tx,
+    });
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(listener, router(state.clone())).into_future());
+
+    TestServer {
+        state,
+        reader_rx: Some(reader_rx),
+        url,
+        _dir: dir,
+    }
+}
+
+impl TestServer {
+    // started after connecting, so the socket sees the timeline from the start
+    fn play(&mut self, script: &str) {
+        self.play_timeline(Timeline::parse(script, Path::new(".")).unwrap());
+    }
+
+    fn play_timeline(&mut self, timeline: Timeline) {
+        let state = self.state.clone();
+        let reader_rx = self.reader_rx.take().expect("one reader per server");
+        std::thread::spawn(move || {
+            media_reader::run_reader(
+                ScriptedMediaReader::from_timeline(timeline),
+                &state,
+                reader_rx,
+                TEST_POLL_INTERVAL,
+            )
+        });
+    }
+
+    async fn connect(&self) -> Socket {
+        let (socket, _) = tokio_tungstenite::connect_async(self.url.as_str())
+            .await
+            .unwrap();
+        socket
+    }
+
+    // one that may send control messages
+    async fn connect_with_token(&self) -> Socket {
+        let url = format!("{}?token={TOKEN}", self.url);
+        let (socket, _) = tokio_tungstenite::connect_async(url).await.unwrap();
+        socket
+    }
+}
+
+async fn next_message(socket: &mut Socket) -> ServerMessage {
+    loop {
+        let msg = tokio::time::timeout(Duration::from_secs(5), socket.next())
+            .await
+            .expect("no message within 5s")
+            .expect("socket closed")
+            .unwrap();
+        if let Message::Text(text) = msg {
+            return serde_json::from_str(&text).unwrap();
+        }
+    }
+}
+
+async fn next_song_event(socket: &mut Socket) -> PlayerEvent {
+    loop {
+        if let ServerMessage::Song(event) = next_message(socket).await {
+            return event;
+        }
+    }
+}
+
+// skips position ticks until something else about the song changes
+async fn next_distinct_event(socket: &mut Socket, last: &PlayerEvent) -> PlayerEvent {
+    loop {
+        let event = next_song_event(socket).await;
+        let same = match (&event, last) {
+            (PlayerEvent::TrackChanged(a), PlayerEvent::TrackChanged(b)) => {
+                a.title == b.title && a.is_playing == b.is_playing
+            }
+            (a, b) => a == b,
+        };
+        if !same {
+            return event;
+        }
+    }
+}
+
+fn song_of(event: &PlayerEvent) -> (&str, bool) {
+    let song = event.song().expect("a track");
+    (song.title.as_str(), song.is_playing)
+}
+
+#[tokio::test]
+async fn ws_sends_stopped_on_connect_when_nothing_plays() {
+    let server = start_server().await;
+    let mut socket = server.connect().await;
+
+    assert_eq!(next_song_event(&mut socket).await, PlayerEvent::Stopped);
+}
+
+#[tokio::test]
+async fn ws_sends_the_current_config_on_connect() {
+    let server = start_server().await;
+    let config = OverlayConfig {
+        theme: "vinyl".to_string(),
+        ..OverlayConfig::default()
+    };
+    // changed while nothing was connected
+    server.state.config_manager.update_config(config).unwrap();
+    let mut socket = server.connect().await;
+
+    match next_message(&mut socket).await {
+        ServerMessage::Config(config) => assert_eq!(config.theme, "vinyl"),
+        other => panic!("expected the config first, got {other:?}"),
+    }
+    assert_eq!(next_song_event(&mut socket).await, PlayerEvent::Stopped);
+}
+
+#[tokio::test]
+async fn ws_follows_the_scripted_timeline() {
+    let mut server = start_server().await;
+    let mut socket = server.connect().await;
+    let initial = next_song_event(&mut socket).await;
+
+    server.play(
+        r#"{"steps": [
+            {"at_ms": 0, "action": "play", "track": {"title": "One", "artist": "A", "length_secs": 100}},
+            {"at_ms": 200, "action": "pause"},
+            {"at_ms": 400, "action": "play", "track": {"title": "Two", "artist": "B", "length_secs": 100}},
+            {"at_ms": 600, "action": "stop"},
+            {"at_ms": 800, "action": "gone"}
+        ]}"#,
+    );
+
+    let one = next_distinct_event(&mut socket, &initial).await;
+    assert_eq!(song_of(&one), ("One", true));
+    assert_eq!(one.song().unwrap().artist, "A");
+    let paused = next_distinct_event(&mut socket, &one).await;
+    assert_eq!(song_of(&paused), ("One", false));
+    let two = next_distinct_event(&mut socket, &paused).await;
+    assert_eq!(song_of(&two), ("Two", true));
+    let stopped = next_distinct_event(&mut socket, &two).await;
+    assert_eq!(stopped, PlayerEvent::Stopped);
+    let gone = next_distinct_event(&mut socket, &stopped).await;
+    assert_eq!(gone, PlayerEvent::PlayerGone);
+}
+
+#[tokio::test]
+async fn reconnecting_socket_gets_the_current_track() {
+    let mut server = start_server().await;
+    server.play(
+        r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Now", "length_secs": 100}}]}"#,
+    );
+    // wait for the reader to publish it
+    let mut first = server.connect().await;
+    let initial = next_song_event(&mut first).await;
+    if initial == PlayerEvent::Stopped {
+        next_distinct_event(&mut first, &initial).await;
+    }
+
+    let mut second = server.connect().await;
+    assert_eq!(song_of(&next_song_event(&mut second).await), ("Now", true));
+}
+
+#[tokio::test]
+async fn ws_control_messages_reach_the_player() {
+    let mut server = start_server().await;
+    let mut socket = server.connect_with_token().await;
+    let initial = next_song_event(&mut socket).await;
+    server.play(
+        r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Song", "length_secs": 300}}]}"#,
+    );
+    let playing = next_distinct_event(&mut socket, &initial).await;
+    assert_eq!(song_of(&playing), ("Song", true));
+
+    socket
+        .send(Message::Text(r#"{"action": "play_pause"}"#.into()))
+        .await
+        .unwrap();
+    let paused = next_distinct_event(&mut socket, &playing).await;
+    assert_eq!(song_of(&paused), ("Song", false));
+
+    socket
+        .send(Message::Text(
+            r#"{"action": "seek_to", "position_secs": 120}"#.into(),
+        ))
+        .await
+        .unwrap();
+    loop {
+        let event = next_song_event(&mut socket).await;
+        if event.song().unwrap().position_secs == 120 {
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn huge_seeks_are_rejected_and_the_reader_keeps_going() {
+    let mut server = start_server().await;
+    let mut socket = server.connect().await;
+    let initial = next_song_event(&mut socket).await;
+    server.play(
+        r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Song", "length_secs": 300}}]}"#,
+    );
+    let playing = next_distinct_event(&mut socket, &initial).await;
+    let post = |uri: &str, body: &str| {
+        let request = Request::post(uri)
+            .header(header::CONTENT_TYPE, "application/json")
+            .header(header::AUTHORIZATION, format!("Bearer {TOKEN}"))
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        router(server.state.clone()).oneshot(request)
+    };
+
+    for position in ["1e14", "1e20", "1e300", "-1"] {
+        let response = post(
+            "/api/player/seek",
+            &format!(r#"{{"position_secs": {position}}}"#),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            response.status(),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "{position}"
+        );
+    }
+
+    let response = post("/api/player/play_pause", "").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let paused = next_distinct_event(&mut socket, &playing).await;
+    assert_eq!(song_of(&paused), ("Song", false));
+
+    // past the end lands on the end
+    let response = post("/api/player/seek", r#"{"position_secs": 1e9}"#)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    loop {
+        let event = next_song_event(&mut socket).await;
+        if event.song().is_some_and(|song| song.position_secs == 300) {
+            break;
+        }
+    }
+}
+
+// counts polls, and hands out the watching flag so the test can drop the "connection"
+struct WatchingReader {
+    polls: Arc<AtomicUsize>,
+    watching: Arc<Mutex<Option<Arc<AtomicBool>>>>,
+}
+
+impl MediaReader for WatchingReader {
+    fn new() -> Self {
+        unreachable!()
+    }
+
+    fn poll(&self) -> PlayerEvent {
+        self.polls.fetch_add(1, Ordering::Relaxed);
+        PlayerEvent::Stopped
+    }
+
+    fn set_player_selection(&self, _selection: PlayerSelection) {}
+
+    fn list_players(&self) -> Vec<PlayerSummary> {
+        Vec::new()
+    }
+
+    fn watch_changes(&self, _notify: Arc<dyn Fn() + Send + Sync>, watching: Arc<AtomicBool>) {
+        watching.store(true, Ordering::Relaxed);
+        *self.watching.lock().unwrap() = Some(watching);
+    }
+}
+
+impl MediaController for WatchingReader {
+    fn play_pause(&self) -> Result<(), ControlError> {
+        Err(ControlError::Unsupported)
+    }
+
+    fn next(&self) -> Result<(), ControlError> {
+        Err(ControlError::Unsupported)
+    }
+
+    fn previous(&self) -> Result<(), ControlError> {
+        Err(ControlError::Unsupported)
+    }
+
+    fn seek_to(&self, _position_secs: f64) -> Result<(), ControlError> {
+        Err(ControlError::Unsupported)
+    }
+
+    fn set_volume(&self, _volume: f64) -> Result<(), ControlError> {
+        Err(ControlError::Unsupported)
+    }
+}
+
+#[tokio::test]
+async fn idle_reader_speeds_back_up_when_the_watcher_dies() {
+    let mut server = start_server().await;
+    let polls = Arc::new(AtomicUsize::new(0));
+    let watching = Arc::new(Mutex::new(None));
+    let reader = WatchingReader {
+        polls: polls.clone(),
+        watching: watching.clone(),
+    };
+    let state = server.state.clone();
+    let reader_rx = server.reader_rx.take().unwrap();
+    std::thread::spawn(move || {
+        media_reader::run_reader(reader, &state, reader_rx, TEST_POLL_INTERVAL)
+    });
+    let window = TEST_POLL_INTERVAL * 20;
+
+    // idle and watched: one poll per 5 ticks
+    tokio::time::sleep(TEST_POLL_INTERVAL).await;
+    let before = polls.load(Ordering::Relaxed);
+    tokio::time::sleep(window).await;
+    let watched = polls.load(Ordering::Relaxed) - before;
+
+    let flag = watching.lock().unwrap().clone().unwrap();
+    flag.store(false, Ordering::Relaxed);
+    // the pass already waiting still uses the long delay
+    tokio::time::sleep(TEST_POLL_INTERVAL * 6).await;
+    let before = polls.load(Ordering::Relaxed);
+    tokio::time::sleep(window).await;
+    let fallback = polls.load(Ordering::Relaxed) - before;
+
+    assert!(watched <= 6, "{watched} polls while watching");
+    assert!(fallback >= 12, "{fallback} polls after losing the watcher");
+}
+
+#[tokio::test]
+async fn config_updates_are_pushed_to_ws() {
+    let server = start_server().await;
+    let mut socket = server.connect().await;
+    next_song_event(&mut socket).await;
+
+    let config = OverlayConfig {
+        theme: "vinyl".to_string(),
+        ..OverlayConfig::default()
+    };
+    server.state.config_manager.update_config(config).unwrap();
+
+    match next_message(&mut socket).await {
+        ServerMessage::Config(config) => assert_eq!(config.theme, "vinyl"),
+        other => panic!("expected a config message, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn album_art_is_served_by_hash_instead_of_inline() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("cover.png"), b"\x89PNG\r\n\x1a\n").unwrap();
+    let script = dir.path().join("script.json");
+    std::fs::write(
+        &script,
+        r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Art", "art": "cover.png"}}]}"#,
+    )
+    .unwrap();
+
+    let mut server = start_server().await;
+    let mut socket = server.connect().await;
+    let initial = next_song_event(&mut socket).await;
+    server.play_timeline(Timeline::load(&script).unwrap());
+    let event = next_distinct_event(&mut socket, &initial).await;
+    let song = event.song().unwrap();
+    assert_eq!(song.album_art_base64, None);
+    let url = song.album_art_url.clone().expect("an art url");
+
+    let response = router(server.state.clone())
+        .oneshot(Request::get(url.as_str()).body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "image/png");
+    assert!(
+        response.headers()[header::CACHE_CONTROL]
+            .to_str()
+            .unwrap()
+            .contains("immutable")
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"\x89PNG\r\n\x1a\n");
+
+    let missing = router(server.state.clone())
+        .oneshot(Request::get("/api/art/nope").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn inline_album_art_flag_restores_base64() {
+    let mut server = start_server().await;
+    let config = OverlayConfig {
+        inline_album_art: true,
+        ..OverlayConfig::default()
+    };
+    server.state.config_manager.update_config(config).unwrap();
+    let mut socket = server.connect().await;
+    let initial = next_song_event(&mut socket).await;
+
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("cover.png"), b"png").unwrap();
+    let timeline = Timeline::parse(
+        r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Art", "art": "cover.png"}}]}"#,
+        dir.path(),
+    )
+    .unwrap();
+    server.play_timeline(timeline);
+
+    let event = next_distinct_event(&mut socket, &initial).await;
+    let song = event.song().unwrap();
+    assert!(song.album_art_url.is_some());
+    assert_eq!(
+        song.album_art_base64.as_deref().map(String::as_str),
+        Some("cG5n")
+    );
+}
+
+#[tokio::test]
+async fn remote_album_art_is_fetched_and_pushed() {
+    let cover = axum::Router::new().route(
+        "/cover.png",
+        axum::routing::get(|| async { b"\x89PNG\r\n\x1a\n".as_slice() }),
+    );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let art_url = format!("http://{}/cover.png", listener.local_addr().unwrap());
+    tokio::spawn(axum::serve(listener, cover).into_future());
+
+    let mut server = start_server().await;
+    let mut socket = server.connect().await;
+    next_song_event(&mut socket).await;
+    server.play(&format!(
+        r#"{{"steps": [{{"at_ms": 0, "action": "play", "track": {{"title": "Remote", "art": "{art_url}"}}}}]}}"#
+    ));
+
+    // no art until the download is done, then the reader is woken up
+    loop {
+        let event = next_song_event(&mut socket).await;
+        if let Some(url) = event.song().and_then(|song| song.album_art_url.clone()) {
+            let art = server.state.art.get(url.rsplit('/').next().unwrap());
+            assert_eq!(art.unwrap().content_type, "image/png");
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn finished_tracks_show_up_in_history() {
+    let mut server = start_server().await;
+    let mut socket = server.connect().await;
+    let initial = next_song_event(&mut socket).await;
+    server.play(
+        r#"{"steps": [
+            {"at_ms": 0, "action": "play", "track": {"title": "One", "artist": "A", "length_secs": 100}},
+            {"at_ms": 1200, "action": "play", "track": {"title": "Two", "artist": "B", "length_secs": 100}},
+            {"at_ms": 2400, "action": "stop"}
+        ]}"#,
+    );
+    // stopping ends the second play
+    let mut last = next_distinct_event(&mut socket, &initial).await;
+    while last != PlayerEvent::Stopped {
+        last = next_distinct_event(&mut socket, &last).await;
+    }
+
+    let history = |uri: &'static str| {
+        let app = router(server.state.clone());
+        async move {
+            let response = app
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            serde_json::from_slice::<Vec<HistoryEntry>>(&body).unwrap()
+        }
+    };
+
+    let entries = history("/api/history").await;
+    let titles: Vec<_> = entries.iter().map(|e| e.title.as_str()).collect();
+    assert_eq!(titles, ["Two", "One"]);
+    assert_eq!(entries[1].artist, "A");
+    assert_eq!(entries[1].player.as_deref(), Some("Scripted"));
+    assert!(entries[1].played_secs >= 1);
+
+    assert_eq!(history("/api/history?limit=1").await.len(), 1);
+    assert!(history("/api/history?since=99999999999").await.is_empty());
+}
+
+#[tokio::test]
+async fn now_playing_text_renders_templates() {
+    let mut server = start_server().await;
+    let mut socket = server.connect().await;
+    let initial = next_song_event(&mut socket).await;
+    server.play(
+        r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Song", "artist": "Band", "length_secs": 200}}]}"#,
+    );
+    next_distinct_event(&mut socket, &initial).await;
+
+    let get = |uri: &'static str| {
+        let app = router(server.state.clone());
+        async move {
+            let response = app
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+    };
+
+    assert_eq!(
+        get("/api/now-playing.txt").await,
+        (StatusCode::OK, "Band - Song".to_string())
+    );
+    let (status, body) =
+        get("/api/now-playing.txt?template=%7Btitle%7Cupper%7D%20%2F%20%7Blength%7D").await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "SONG / 3:20"));
+    let (status, body) = get("/api/now-playing.txt?template=%7Bnope%7D").await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("unknown field"));
+}
+
+#[tokio::test]
+async fn oversized_text_templates_are_rejected() {
+    let server = start_server().await;
+    let get = |template: String| {
+        let app = router(server.state.clone());
+        async move {
+            let uri = format!("/api/now-playing.txt?template={template}");
+            let response = app
+                .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+    };
+
+    // {?title} ... {/title}, 50 deep and still under the length cap
+    let nested = "%7B%3Ftitle%7D".repeat(50) + &"%7B%2Ftitle%7D".repeat(50);
+    let (status, body) = get(nested).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("nested"));
+
+    let (status, body) = get("x".repeat(2000)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert!(body.contains("longer than"));
+}
+
+#[tokio::test]
+async fn now_playing_json_supports_etags() {
+    let mut server = start_server().await;
+    let state = server.state.clone();
+    let get = |if_none_match: Option<String>| {
+        let app = router(state.clone());
+        let mut request = Request::get("/api/now-playing");
+        if let Some(etag) = if_none_match {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        app.oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    assert_eq!(get(None).await.unwrap().status(), StatusCode::NO_CONTENT);
+
+    let mut socket = server.connect().await;
+    let initial = next_song_event(&mut socket).await;
+    server.play(
+        r#"{"steps": [
+            {"at_ms": 0, "action": "play", "track": {"title": "Song", "length_secs": 200}},
+            {"at_ms": 0, "action": "pause"}
+        ]}"#,
+    );
+    let paused = next_distinct_event(&mut socket, &initial).await;
+    assert_eq!(song_of(&paused), ("Song", false));
+
+    let response = get(None).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let etag = response.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let song: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(song["title"], "Song");
+
+    let cached = get(Some(etag.clone())).await.unwrap();
+    assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(cached.headers()[header::ETAG], etag.as_str());
+    let stale = get(Some("\"other\"".to_string())).await.unwrap();
+    assert_eq!(stale.status(), StatusCode::OK);
+}
+
+// reads sse frames off the raw body until `count` events came in
+async fn sse_events(
+    body: &mut (impl futures::Stream<Item = Result<axum::body::Bytes, axum::Error>> + Unpin),
+    count: usize,
+) -> Vec<(String, String, serde_json::Value)> {
+    let mut buffer = String::new();
+    let mut events = Vec::new();
+    while events.len() < count {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), body.next())
+            .await
+            .expect("no event within 5s")
+            .expect("stream ended")
+            .unwrap();
+        buffer.push_str(std::str::from_utf8(&chunk).unwrap());
+        while let Some(end) = buffer.find("\n\n") {
+            let frame: String = buffer.drain(..end + 2).collect();
+            let field = |name: &str| {
+                frame
+                    .lines()
+                    .find_map(|line| line.strip_prefix(name))
+                    .map(str::to_string)
+            };
+            // keep-alive comments have no event
+            if let (Some(id), Some(event), Some(data)) =
+                (field("id: "), field("event: "), field("data: "))
+            {
+                events.push((id, event, serde_json::from_str(&data).unwrap()));
+            }
+        }
+    }
+    events
+}
+
+#[tokio::test]
+async fn sse_sends_state_then_changes_and_resumes() {
+    let mut server = start_server().await;
+    let response = router(server.state.clone())
+        .oneshot(Request::get("/api/events").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/event-stream"
+    );
+    let mut body = response.into_body().into_data_stream();
+
+    let initial = sse_events(&mut body, 3).await;
+    let kinds: Vec<_> = initial.iter().map(|(_, kind, _)| kind.as_str()).collect();
+    assert_eq!(kinds, ["config", "players", "song"]);
+    assert_eq!(initial[2].2["type"], "stopped");
+
+    server.play(
+        r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Live", "length_secs": 100}}]}"#,
+    );
+    let mut seen = Vec::new();
+    loop {
+        let event = sse_events(&mut body, 1).await.remove(0);
+        let live = event.1 == "song" && event.2["song"]["title"] == "Live";
+        seen.push(event);
+        if live {
+            break;
+        }
+    }
+    assert!(seen.iter().any(|(_, kind, _)| kind == "players"));
+
+    // resuming from the first live event replays the ones after it
+    let (first_id, _, _) = seen[0].clone();
+    let resumed = router(server.state.clone())
+        .oneshot(
+            Request::get("/api/events")
+                .header("last-event-id", first_id.as_str())
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let mut body = resumed.into_body().into_data_stream();
+    let replayed = sse_events(&mut body, 1).await;
+    let number = |id: &str| server.state.events.parse_id(id).unwrap();
+    assert_eq!(number(&replayed[0].0), number(&first_id) + 1);
+
+    // ids from before a restart start over with the full state
+    let stale = router(server.state.clone())
+        .oneshot(
+            Request::get("/api/events")
+                .header("last-event-id", "1")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let mut body = stale.into_body().into_data_stream();
+    let kinds: Vec<_> = sse_events(&mut body, 3)
+        .await
+        .into_iter()
+        .map(|(_, kind, _)| kind)
+        .collect();
+    assert_eq!(kinds, ["config", "players", "song"]);
+}
+
+#[tokio::test]
+async fn ws_control_needs_the_token() {
+    let mut server = start_server().await;
+    let mut watcher = server.connect().await;
+    let initial = next_song_event(&mut watcher).await;
+    server.play(
+        r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Song", "length_secs": 300}}]}"#,
+    );
+    next_distinct_event(&mut watcher, &initial).await;
+
+    watcher
+        .send(Message::Text(r#"{"action": "play_pause"}"#.into()))
+        .await
+        .unwrap();
+    let mut controller = server.connect_with_token().await;
+    controller
+        .send(Message::Text(
+            r#"{"action": "seek_to", "position_secs": 120}"#.into(),
+        ))
+        .await
+        .unwrap();
+
+    loop {
+        let event = next_song_event(&mut watcher).await;
+        let song = event.song().unwrap();
+        if song.position_secs >= 120 {
+            assert!(song.is_playing, "play_pause without a token went through");
+            break;
+        }
+    }
+}
+
+#[tokio::test]
+async fn write_routes_need_the_token() {
+    let server = start_server().await;
+    let post = |uri: &str, auth: Option<&str>| {
+        let mut request = Request::post(uri).header(header::CONTENT_TYPE, "application/json");
+        if let Some(auth) = auth {
+            request = request.header(header::AUTHORIZATION, auth);
+        }
+        let body = serde_json::to_string(&OverlayConfig::default()).unwrap();
+        router(server.state.clone()).oneshot(request.body(Body::from(body)).unwrap())
+    };
+
+    let denied = post("/api/config", None).await.unwrap();
+    assert_eq!(denied.status(), StatusCode::UNAUTHORIZED);
+    let wrong = post("/api/config", Some("Bearer nope")).await.unwrap();
+    assert_eq!(wrong.status(), StatusCode::UNAUTHORIZED);
+    let header = post("/api/config", Some("Bearer test-token"))
+        .await
+        .unwrap();
+    assert_eq!(header.status(), StatusCode::OK);
+    let query = post("/api/config?token=test-token", None).await.unwrap();
+    assert_eq!(query.status(), StatusCode::OK);
+    let control = post("/api/player/play_pause", None).await.unwrap();
+    assert_eq!(control.status(), StatusCode::UNAUTHORIZED);
+
+    // reading stays open for obs
+    let config = router(server.state.clone())
+        .oneshot(Request::get("/api/config").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(config.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn cors_only_allows_configured_origins() {
+    let server = start_server().await;
+    let preflight = |origin: &'static str| {
+        router(server.state.clone()).oneshot(
+            Request::options("/api/config")
+                .header(header::ORIGIN, origin)
+                .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+                .body(Body::empty())
+                .unwrap(),
+        )
+    };
+
+    let allowed = preflight("https://allowed.example").await.unwrap();
+    assert_eq!(
+        allowed.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+        "https://allowed.example"
+    );
+    let other = preflight("https://evil.example").await.unwrap();
+    assert!(
+        other
+            .headers()
+            .get(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+            .is_none()
+    );
+}
+
+#[tokio::test]
+async fn pages_are_served_from_the_binary() {
+    let server = start_server().await;
+    let get = |uri: &'static str, etag: Option<String>| {
+        let mut request = Request::get(uri);
+        if let Some(etag) = etag {
+            request = request.header(header::IF_NONE_MATCH, etag);
+        }
+        router(server.state.clone()).oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    let overlay = get("/", None).await.unwrap();
+    assert_eq!(overlay.status(), StatusCode::OK);
+    assert_eq!(
+        overlay.headers()[header::CONTENT_TYPE],
+        "text/html; charset=utf-8"
+    );
+    let etag = overlay.headers()[header::ETAG]
+        .to_str()
+        .unwrap()
+        .to_string();
+    let body = axum::body::to_bytes(overlay.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(String::from_utf8_lossy(&body).contains("overlay.js"));
+
+    let cached = get("/", Some(etag)).await.unwrap();
+    assert_eq!(cached.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(
+        get("/customize", None).await.unwrap().status(),
+        StatusCode::OK
+    );
+    assert_eq!(
+        get("/nope.js", None).await.unwrap().status(),
+        StatusCode::NOT_FOUND
+    );
+}
+
+// a second process running `currentsong config set` / `now` against this server
+fn publish_instance(server: &TestServer, settings: &str) -> (InstanceLock, PathBuf) {
+    let dir = server._dir.path();
+    std::fs::write(dir.join(settings::SETTINGS_FILE), settings).unwrap();
+    let Acquired::Primary(lock) = InstanceLock::acquire(dir).unwrap() else {
+        panic!("nothing else should hold the lock");
+    };
+    let base_url = server.url.replace("ws://", "http://");
+    lock.publish(&InstanceInfo {
+        pid: std::process::id(),
+        base_url: base_url.trim_end_matches("/ws").to_string(),
+    })
+    .unwrap();
+    (lock, dir.join("config.json"))
+}
+
+#[tokio::test]
+async fn cli_commands_are_forwarded_to_the_running_server() {
+    let mut server = start_server().await;
+    let (_lock, config_path) = publish_instance(&server, r#"{"auth": {"token": "test-token"}}"#);
+    let mut socket = server.connect().await;
+    let initial = next_song_event(&mut socket).await;
+
+    // 204 from the server is nothing playing
+    let idle = commands::now(&config_path, None, true).await.unwrap();
+    assert_eq!(idle, "null");
+
+    let set = ConfigCommand::Set {
+        key: "theme".to_string(),
+        value: "vinyl".to_string(),
+    };
+    assert_eq!(commands::config(&config_path, set).await.unwrap(), "");
+    assert_eq!(server.state.config_manager.get_config().theme, "vinyl");
+    match next_message(&mut socket).await {
+        ServerMessage::Config(config) => assert_eq!(config.theme, "vinyl"),
+        other => panic!("expected the new config, got {other:?}"),
+    }
+    let get = ConfigCommand::Get {
+        key: Some("theme".to_string()),
+    };
+    assert_eq!(commands::config(&config_path, get).await.unwrap(), "vinyl");
+
+    server.play(
+        r#"{"steps": [{"at_ms": 0, "action": "play", "track": {"title": "Remote", "artist": "Server", "length_secs": 100}}]}"#,
+    );
+    next_distinct_event(&mut socket, &initial).await;
+    let now = commands::now(&config_path, Some("{title} by {artist}"), false)
+        .await
+        .unwrap();
+    assert_eq!(now, "Remote by Server");
+}
+
+#[tokio::test]
+async fn forwarded_config_changes_need_the_same_token() {
+    let server = start_server().await;
+    let (_lock, config_path) = publish_instance(&server, r#"{"auth": {"token": "other"}}"#);
+
+    let set = ConfigCommand::Set {
+        key: "theme".to_string(),
+        value: "vinyl".to_string(),
+    };
+    let err = commands::config(&config_path, set).await.unwrap_err();
+
+    assert!(err.contains("401"), "{err}");
+    assert_eq!(
+        server.state.config_manager.get_config().theme,
+        OverlayConfig::default().theme
+    );
+}
@@ -0,0 +1,78 @@
+use crate::auth::AuthSettings;
+use crate::cli::ServeArgs;
+use crate::history::HistorySettings;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+
+pub const SETTINGS_FILE: &str = "settings.json";
+
+// app level settings, unlike OverlayConfig these are never sent to overlays
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub host: String,
+    pub port: u16,
+    pub history: HistorySettings,
+    pub auth: AuthSettings,
+    // where file output writes, only settable here since it picks what gets overwritten
+    pub file_output_dir: Option<PathBuf>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            host: "127.0.0.1".to_string(),
+            port: 3333,
+            history: HistorySettings::default(),
+            auth: AuthSettings::default(),
+            file_output_dir: None,
+        }
+    }
+}
+
+impl Settings {
+    // precedence: cli flag > env var > settings.json > default
+    pub fn load(args: &ServeArgs, path: &Path) -> Self {
+        let mut settings = Self::from_file(path);
+
+        if let Some(host) = &args.host {
+            settings.host = host.clone();
+        }
+        if let Some(port) = args.port {
+            settings.port = port;
+        }
+
+        settings
+    }
+
+    fn from_file(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(content) => serde_json::from_str(&content).unwrap_or_else(|e| {
+                eprintln!("ignoring {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(_) => Self::default(),
+        }
+    }
+
+    // what the server binds to, "host:port"
+    pub fn bind_address(&self) -> String {
+        match self.host.parse::<IpAddr>() {
+            Ok(IpAddr::V6(ip)) => format!("[{ip}]:{}", self.port),
+            _ => format!("{}:{}", self.host, self.port),
+        }
+    }
+
+    // what a local browser should open, 0.0.0.0 is not browsable
+    pub fn base_url(&self) -> String {
+        match self.host.parse::<IpAddr>() {
+            Ok(ip) if ip.is_unspecified() => format!("http://127.0.0.1:{}", self.port),
+            _ => format!("http://{}", self.bind_address()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
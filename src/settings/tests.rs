@@ -0,0 +1,43 @@
+use super::Settings;
+
+fn settings(host: &str, port: u16) -> Settings {
+    Settings {
+        host: host.to_string(),
+        port,
+        ..Settings::default()
+    }
+}
+
+#[test]
+fn default_matches_previous_hardcoded_address() {
+    let settings = Settings::default();
+
+    assert_eq!(settings.bind_address(), "127.0.0.1:3333");
+    assert_eq!(settings.base_url(), "http://127.0.0.1:3333");
+}
+
+#[test]
+fn unspecified_host_opens_loopback_in_browser() {
+    assert_eq!(settings("0.0.0.0", 4000).bind_address(), "0.0.0.0:4000");
+    assert_eq!(
+        settings("0.0.0.0", 4000).base_url(),
+        "http://127.0.0.1:4000"
+    );
+    assert_eq!(settings("::", 4000).base_url(), "http://127.0.0.1:4000");
+}
+
+#[test]
+fn ipv6_host_is_bracketed() {
+    assert_eq!(settings("::1", 4000).bind_address(), "[::1]:4000");
+    assert_eq!(settings("::1", 4000).base_url(), "http://[::1]:4000");
+}
+
+#[test]
+fn missing_keys_in_file_fall_back_to_defaults() {
+    let settings: Settings = serde_json::from_str(r#"{ "port": 8080 }"#).unwrap();
+
+    assert_eq!(settings.host, "127.0.0.1");
+    assert_eq!(settings.port, 8080);
+    assert!(settings.history.enabled);
+    assert_eq!(settings.file_output_dir, None);
+}
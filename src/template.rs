@@ -0,0 +1,326 @@
+use crate::models::SongInfo;
+use std::fmt;
+
+// "Artist - Title", or just the title
+pub const DEFAULT_TEMPLATE: &str = "{?artist}{artist} - {/artist}{title}";
+// parsing and rendering recurse per section, so nesting is kept shallow
+const MAX_DEPTH: usize = 16;
+
+// text with {field|filter|filter:arg} placeholders, e.g.
+// "{artist} — {title|truncate:40}{?length} [{position}/{length}]{/length}"
+//   {?field}...{/field}  only when the field isn't empty (or 0)
+//   {!field}...{/field}  only when it is
+//   {{ and }}            literal braces
+#[derive(Debug, Clone)]
+pub struct Template {
+    nodes: Vec<Node>,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Text(String),
+    Field(Field, Vec<Filter>),
+    Section {
+        field: Field,
+        when_empty: bool,
+        body: Vec<Node>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Field {
+    Title,
+    Artist,
+    Album,
+    // times render as m:ss unless filtered
+    Position,
+    Length,
+    Remaining,
+    // playing, paused or stopped
+    Status,
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Self, TemplateError> {
+        Ok(match name {
+            "title" => Field::Title,
+            "artist" => Field::Artist,
+            "album" => Field::Album,
+            "position" => Field::Position,
+            "length" => Field::Length,
+            "remaining" => Field::Remaining,
+            "status" => Field::Status,
+            _ => return Err(TemplateError::UnknownField(name.to_string())),
+        })
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Field::Title => "title",
+            Field::Artist => "artist",
+            Field::Album => "album",
+            Field::Position => "position",
+            Field::Length => "length",
+            Field::Remaining => "remaining",
+            Field::Status => "status",
+        }
+    }
+
+    fn value(self, song: Option<&SongInfo>) -> Value {
+        let Some(song) = song else {
+            return match self {
+                Field::Status => Value::Text("stopped".to_string()),
+                Field::Position | Field::Length | Field::Remaining => Value::Secs(0),
+                _ => Value::Text(String::new()),
+            };
+        };
+        match self {
+            Field::Title => Value::Text(song.title.clone()),
+            Field::Artist => Value::Text(song.artist.clone()),
+            Field::Album => Value::Text(song.album.clone()),
+            Field::Position => Value::Secs(song.position_secs),
+            Field::Length => Value::Secs(song.length_secs),
+            Field::Remaining => Value::Secs(song.length_secs.saturating_sub(song.position_secs)),
+            Field::Status => Value::Text(if song.is_playing { "playing" } else { "paused" }.into()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Filter {
+    // m:ss, or h:mm:ss past an hour
+    Time,
+    // always h:mm:ss
+    Hms,
+    // plain number of seconds
+    Secs,
+    Upper,
+    Lower,
+    // at most n characters, the last one an ellipsis when cut
+    Truncate(usize),
+}
+
+impl Filter {
+    fn parse(spec: &str) -> Result<Self, TemplateError> {
+        let (name, arg) = match spec.split_once(':') {
+            Some((name, arg)) => (name, Some(arg)),
+            None => (spec, None),
+        };
+        let filter = match (name, arg) {
+            ("time", None) => Filter::Time,
+            ("hms", None) => Filter::Hms,
+            ("secs", None) => Filter::Secs,
+            ("upper", None) => Filter::Upper,
+            ("lower", None) => Filter::Lower,
+            ("truncate", Some(n)) => Filter::Truncate(
+                n.parse()
+                    .map_err(|_| TemplateError::BadArgument(spec.to_string()))?,
+            ),
+            ("time" | "hms" | "secs" | "upper" | "lower", Some(_)) | ("truncate", None) => {
+                return Err(TemplateError::BadArgument(spec.to_string()));
+            }
+            _ => return Err(TemplateError::UnknownFilter(name.to_string())),
+        };
+        Ok(filter)
+    }
+
+    fn apply(&self, value: Value) -> Value {
+        match (self, value) {
+            (Filter::Time, Value::Secs(secs)) => Value::Text(time(secs)),
+            (Filter::Hms, Value::Secs(secs)) => Value::Text(hms(secs)),
+            (Filter::Secs, Value::Secs(secs)) => Value::Text(secs.to_string()),
+            // time filters leave text alone
+            (Filter::Time | Filter::Hms | Filter::Secs, value) => value,
+            (Filter::Upper, value) => Value::Text(value.render().to_uppercase()),
+            (Filter::Lower, value) => Value::Text(value.render().to_lowercase()),
+            (Filter::Truncate(n), value) => Value::Text(truncate(&value.render(), *n)),
+        }
+    }
+}
+
+enum Value {
+    Text(String),
+    Secs(u64),
+}
+
+impl Value {
+    fn is_empty(&self) -> bool {
+        match self {
+            Value::Text(text) => text.is_empty(),
+            Value::Secs(secs) => *secs == 0,
+        }
+    }
+
+    fn render(self) -> String {
+        match self {
+            Value::Text(text) => text,
+            Value::Secs(secs) => time(secs),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TemplateError {
+    UnknownField(String),
+    UnknownFilter(String),
+    BadArgument(String),
+    // a { without its }
+    UnclosedTag,
+    UnclosedSection(String),
+    UnexpectedClose(String),
+    TooDeep,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TemplateError::UnknownField(name) => write!(f, "unknown field \"{name}\""),
+            TemplateError::UnknownFilter(name) => write!(f, "unknown filter \"{name}\""),
+            TemplateError::BadArgument(spec) => write!(f, "bad filter argument in \"{spec}\""),
+            TemplateError::UnclosedTag => write!(f, "missing }} (use {{{{ for a literal {{)"),
+            TemplateError::UnclosedSection(name) => write!(f, "missing {{/{name}}}"),
+            TemplateError::UnexpectedClose(name) => write!(f, "{{/{name}}} without a section"),
+            TemplateError::TooDeep => write!(f, "sections nested more than {MAX_DEPTH} deep"),
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        // open sections, innermost last
+        let mut stack: Vec<(Field, bool, Vec<Node>)> = Vec::new();
+        let mut nodes = Vec::new();
+        let mut text = String::new();
+        let mut rest = source;
+
+        while let Some(i) = rest.find(['{', '}']) {
+            text.push_str(&rest[..i]);
+            let brace = &rest[i..i + 1];
+            rest = &rest[i + 1..];
+            if let Some(after) = rest.strip_prefix(brace) {
+                text.push_str(brace);
+                rest = after;
+                continue;
+            }
+            // a lone } is just text
+            if brace == "}" {
+                text.push('}');
+                continue;
+            }
+
+            let end = rest.find('}').ok_or(TemplateError::UnclosedTag)?;
+            let tag = rest[..end].trim();
+            rest = &rest[end + 1..];
+            if !text.is_empty() {
+                nodes.push(Node::Text(std::mem::take(&mut text)));
+            }
+
+            if tag.starts_with(['?', '!']) && stack.len() >= MAX_DEPTH {
+                return Err(TemplateError::TooDeep);
+            }
+            if let Some(name) = tag.strip_prefix('?') {
+                stack.push((
+                    Field::parse(name.trim())?,
+                    false,
+                    std::mem::take(&mut nodes),
+                ));
+            } else if let Some(name) = tag.strip_prefix('!') {
+                stack.push((Field::parse(name.trim())?, true, std::mem::take(&mut nodes)));
+            } else if let Some(name) = tag.strip_prefix('/') {
+                let name = name.trim();
+                let field = Field::parse(name)?;
+                match stack.pop() {
+                    Some((open, when_empty, outer)) if open == field => {
+                        let body = std::mem::replace(&mut nodes, outer);
+                        nodes.push(Node::Section {
+                            field,
+                            when_empty,
+                            body,
+                        });
+                    }
+                    _ => return Err(TemplateError::UnexpectedClose(name.to_string())),
+                }
+            } else {
+                let mut parts = tag.split('|').map(str::trim);
+                let field = Field::parse(parts.next().unwrap_or_default())?;
+                let filters = parts.map(Filter::parse).collect::<Result<_, _>>()?;
+                nodes.push(Node::Field(field, filters));
+            }
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            nodes.push(Node::Text(text));
+        }
+
+        if let Some((field, _, _)) = stack.pop() {
+            return Err(TemplateError::UnclosedSection(field.name().to_string()));
+        }
+        Ok(Template { nodes })
+    }
+
+    // None renders as nothing playing
+    pub fn render(&self, song: Option<&SongInfo>) -> String {
+        let mut out = String::new();
+        render_nodes(&self.nodes, song, &mut out);
+        out
+    }
+}
+
+impl Default for Template {
+    fn default() -> Self {
+        Template::parse(DEFAULT_TEMPLATE).expect("default template parses")
+    }
+}
+
+fn render_nodes(nodes: &[Node], song: Option<&SongInfo>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Field(field, filters) => {
+                let value = filters
+                    .iter()
+                    .fold(field.value(song), |value, filter| filter.apply(value));
+                out.push_str(&value.render());
+            }
+            Node::Section {
+                field,
+                when_empty,
+                body,
+            } => {
+                if field.value(song).is_empty() == *when_empty {
+                    render_nodes(body, song, out);
+                }
+            }
+        }
+    }
+}
+
+fn time(secs: u64) -> String {
+    if secs >= 3600 {
+        hms(secs)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+fn hms(secs: u64) -> String {
+    format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    if max == 0 {
+        return String::new();
+    }
+    let mut cut: String = text.chars().take(max - 1).collect();
+    cut.truncate(cut.trim_end().len());
+    cut.push('…');
+    cut
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,101 @@
+use super::{Template, TemplateError};
+use crate::models::SongInfo;
+
+fn song() -> SongInfo {
+    SongInfo {
+        title: "Title".to_string(),
+        artist: "Artist".to_string(),
+        album: "Album".to_string(),
+        position_secs: 65,
+        length_secs: 3725,
+        is_playing: true,
+        ..SongInfo::default()
+    }
+}
+
+fn render(template: &str, song: Option<&SongInfo>) -> String {
+    Template::parse(template).unwrap().render(song)
+}
+
+#[test]
+fn fields_and_times() {
+    let song = song();
+
+    assert_eq!(
+        render("{artist} — {title} [{position}/{length}]", Some(&song)),
+        "Artist — Title [1:05/1:02:05]"
+    );
+    assert_eq!(
+        render("{position|hms} {remaining|secs} {status}", Some(&song)),
+        "0:01:05 3660 playing"
+    );
+    assert_eq!(render("{ title | upper }", Some(&song)), "TITLE");
+    assert_eq!(render("{{literal}} }", Some(&song)), "{literal} }");
+}
+
+#[test]
+fn sections_hide_empty_fields() {
+    let mut song = song();
+    let template = "{?artist}{artist} - {/artist}{title}{!length} (live){/length}";
+
+    assert_eq!(render(template, Some(&song)), "Artist - Title");
+    song.artist.clear();
+    song.length_secs = 0;
+    assert_eq!(render(template, Some(&song)), "Title (live)");
+
+    let idle = "{!title}nothing playing{/title}{?title}{title}{/title}";
+    assert_eq!(render(idle, None), "nothing playing");
+    assert_eq!(Template::default().render(None), "");
+}
+
+#[test]
+fn truncate_counts_characters() {
+    let song = SongInfo {
+        title: "Ünïcödé title".to_string(),
+        ..SongInfo::default()
+    };
+
+    assert_eq!(render("{title|truncate:8}", Some(&song)), "Ünïcödé…");
+    assert_eq!(render("{title|truncate:9}", Some(&song)), "Ünïcödé…");
+    assert_eq!(render("{title|truncate:50}", Some(&song)), "Ünïcödé title");
+    assert_eq!(render("{title|truncate:3|upper}", Some(&song)), "ÜN…");
+}
+
+#[test]
+fn bad_templates_are_rejected() {
+    let error = |template| Template::parse(template).unwrap_err();
+
+    assert_eq!(error("{nope}"), TemplateError::UnknownField("nope".into()));
+    assert_eq!(
+        error("{title|shout}"),
+        TemplateError::UnknownFilter("shout".into())
+    );
+    assert_eq!(
+        error("{title|truncate:x}"),
+        TemplateError::BadArgument("truncate:x".into())
+    );
+    assert_eq!(error("{title"), TemplateError::UnclosedTag);
+    assert_eq!(
+        error("{?artist}{artist}"),
+        TemplateError::UnclosedSection("artist".into())
+    );
+    assert_eq!(
+        error("{?artist}{/title}"),
+        TemplateError::UnexpectedClose("title".into())
+    );
+}
+
+#[test]
+fn deep_nesting_is_rejected() {
+    let nested = |depth: usize| "{?title}".repeat(depth) + &"{/title}".repeat(depth);
+
+    assert!(Template::parse(&nested(16)).is_ok());
+    assert_eq!(
+        Template::parse(&nested(17)).unwrap_err(),
+        TemplateError::TooDeep
+    );
+    assert_eq!(
+        Template::parse(&nested(10_000)).unwrap_err(),
+        TemplateError::TooDeep
+    );
+}
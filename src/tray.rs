@@ -21,21 +21,21 @@ fn create_icon() -> Icon {
 }
 
 // spawn tra
-pub fn spawn_tray() -> mpsc::Receiver<TrayCommand> {
+pub fn spawn_tray(url: String) -> mpsc::Receiver<TrayCommand> {
     let (cmd_tx, cmd_rx) = mpsc::channel();
 
     std::thread::spawn(move || {
-        run_tray_loop(cmd_tx);
+        run_tray_loop(cmd_tx, &url);
     });
 
     cmd_rx
 }
 
 #[cfg(target_os = "linux")]
-fn run_tray_loop(cmd_tx: mpsc::Sender<TrayCommand>) {
+fn run_tray_loop(cmd_tx: mpsc::Sender<TrayCommand>, url: &str) {
     gtk::init().expect("Failed to init GTK");
 
-    let _tray = build_tray(&cmd_tx);
+    let _tray = build_tray(&cmd_tx, url);
 
     let cmd_tx_clone = cmd_tx.clone();
     glib_recv_menu_events(cmd_tx_clone);
@@ -57,8 +57,8 @@ fn glib_recv_menu_events(cmd_tx: mpsc::Sender<TrayCommand>) {
 }
 
 #[cfg(target_os = "windows")]
-fn run_tray_loop(cmd_tx: mpsc::Sender<TrayCommand>) {
-    let _tray = build_tray(&cmd_tx);
+fn run_tray_loop(cmd_tx: mpsc::Sender<TrayCommand>, url: &str) {
+    let _tray = build_tray(&cmd_tx, url);
 
     // poll menu event
     loop {
@@ -74,7 +74,7 @@ static PREVIEW_ID: &str = "preview";
 static CUSTOMIZE_ID: &str = "customize";
 static QUIT_ID: &str = "quit";
 
-fn build_tray(_cmd_tx: &mpsc::Sender<TrayCommand>) -> tray_icon::TrayIcon {
+fn build_tray(_cmd_tx: &mpsc::Sender<TrayCommand>, url: &str) -> tray_icon::TrayIcon {
     let menu = Menu::new();
 
     let open_item = MenuItem::with_id(PREVIEW_ID, "Preview", true, None);
@@ -90,7 +90,7 @@ fn build_tray(_cmd_tx: &mpsc::Sender<TrayCommand>) -> tray_icon::TrayIcon {
 
     TrayIconBuilder::new()
         .with_menu(Box::new(menu))
-        .with_tooltip("http://127.0.0.1:3333")
+        .with_tooltip(url)
         .with_icon(icon)
         .build()
         .expect("Failed to create tray icon")
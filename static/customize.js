@@ -77,9 +77,9 @@ const PRESETS = {
 // ── DOM ─────────────────────────────────────────────
 const form = document.getElementById('config-form');
 const statusDiv = document.getElementById('status');
+const configWarning = document.getElementById('config-warning');
 const presetGrid = document.getElementById('preset-grid');
 const positionGrid = document.getElementById('position-grid');
-const previewFrame = document.getElementById('preview-frame');
 const customCssEl = document.getElementById('custom-css');
 const importCssEl = document.getElementById('import-css');
 const clearCssBtn = document.getElementById('clear-css');
@@ -96,6 +96,7 @@ const inputs = {
     accent_color: document.getElementById('accent-color'),
     background_color: document.getElementById('background-color'),
     text_color: document.getElementById('text-color'),
+    use_art_colors: document.getElementById('use-art-colors'),
     font_size_px: fontSizeRange,
     border_radius_px: borderRadiusRange,
     blur_px: blurRange,
@@ -103,8 +104,12 @@ const inputs = {
     show_artist: document.getElementById('show-artist'),
     show_progress: document.getElementById('show-progress'),
     show_time: document.getElementById('show-time'),
+    file_output_enabled: document.getElementById('file-output-enabled'),
+    file_output_template: document.getElementById('file-output-template'),
 };
 
+// last config from the server, fields this page doesn't edit are sent back untouched
+let loadedConfig = {};
 let selectedTheme = 'frosted_glass';
 let selectedPosition = 'BottomRight';
 let selectedAnimation = 'slide_up';
@@ -183,13 +188,19 @@ clearCssBtn.addEventListener('click', () => {
     customCssEl.value = '';
 });
 
+// ── Token ───────────────────────────────────────────
+// the tray opens this page with ?token=..., saving needs it
+const token = new URLSearchParams(window.location.search).get('token');
+
 // ── Load Config ─────────────────────────────────────
 fetch('/api/config')
     .then(res => res.json())
     .then(config => {
+        loadedConfig = config;
         inputs.accent_color.value = config.accent_color;
         inputs.background_color.value = config.background_color;
         inputs.text_color.value = config.text_color;
+        inputs.use_art_colors.checked = !!config.use_art_colors;
 
         inputs.font_size_px.value = config.font_size_px;
         fontSizeLabel.textContent = `${config.font_size_px}px`;
@@ -205,6 +216,9 @@ fetch('/api/config')
         inputs.show_progress.checked = config.show_progress;
         inputs.show_time.checked = config.show_time;
 
+        inputs.file_output_enabled.checked = !!config.file_output?.enabled;
+        inputs.file_output_template.value = config.file_output?.nowplaying_template ?? '';
+
         customCssEl.value = config.custom_css || '';
 
         // Theme
@@ -226,15 +240,79 @@ fetch('/api/config')
         });
     });
 
+// ── Config Load Status ──────────────────────────────
+function escapeHtml(text) {
+    const div = document.createElement('div');
+    div.textContent = text;
+    return div.innerHTML;
+}
+
+function loadConfigStatus() {
+    fetch('/api/config/status')
+        .then(res => res.json())
+        .then(status => {
+            if (!status.error) {
+                configWarning.hidden = true;
+                return;
+            }
+            let html = `Could not load <code>${escapeHtml(status.path)}</code>, showing defaults.<br>`
+                + `${escapeHtml(status.error)}`;
+            if (status.backup_path) {
+                html += `<br>Your file was backed up to <code>${escapeHtml(status.backup_path)}</code>.`;
+            }
+            html += '<br>Saving will replace it.';
+            configWarning.innerHTML = html;
+            configWarning.hidden = false;
+        });
+}
+
+loadConfigStatus();
+
+// ── Field Errors ────────────────────────────────────
+function fieldElement(field) {
+    switch (field) {
+        case 'theme': return presetGrid;
+        case 'transition_animation': return animGrid;
+        case 'custom_css': return customCssEl;
+        case 'file_output.nowplaying_template': return inputs.file_output_template.closest('label');
+        default: return inputs[field] ? inputs[field].closest('label') : null;
+    }
+}
+
+function clearFieldErrors() {
+    document.querySelectorAll('.field-error').forEach(el => el.remove());
+    document.querySelectorAll('.has-error').forEach(el => el.classList.remove('has-error'));
+}
+
+// returns the errors that have no input on this page
+function showFieldErrors(errors) {
+    const unmatched = [];
+    errors.forEach(({ field, message }) => {
+        const el = fieldElement(field);
+        if (!el) {
+            unmatched.push(`${field}: ${message}`);
+            return;
+        }
+        el.classList.add('has-error');
+        const msg = document.createElement('div');
+        msg.className = 'field-error';
+        msg.textContent = message;
+        el.insertAdjacentElement('afterend', msg);
+    });
+    return unmatched;
+}
+
 // ── Save Config ─────────────────────────────────────
 form.addEventListener('submit', (e) => {
     e.preventDefault();
 
     const newConfig = {
+        ...loadedConfig,
         theme: selectedTheme,
         accent_color: inputs.accent_color.value,
         background_color: inputs.background_color.value,
         text_color: inputs.text_color.value,
+        use_art_colors: inputs.use_art_colors.checked,
         font_size_px: parseInt(inputs.font_size_px.value),
         border_radius_px: parseInt(inputs.border_radius_px.value),
         blur_px: parseInt(inputs.blur_px.value),
@@ -242,23 +320,42 @@ form.addEventListener('submit', (e) => {
         show_artist: inputs.show_artist.checked,
         show_progress: inputs.show_progress.checked,
         show_time: inputs.show_time.checked,
-        monitor_index: 0,
         position: selectedPosition,
         custom_css: customCssEl.value,
         transition_animation: selectedAnimation,
+        file_output: {
+            ...loadedConfig.file_output,
+            enabled: inputs.file_output_enabled.checked,
+            nowplaying_template: inputs.file_output_template.value,
+        },
     };
 
+    clearFieldErrors();
+
     fetch('/api/config', {
         method: 'POST',
-        headers: { 'Content-Type': 'application/json' },
+        headers: {
+            'Content-Type': 'application/json',
+            ...(token ? { 'Authorization': `Bearer ${token}` } : {}),
+        },
         body: JSON.stringify(newConfig),
     })
         .then(res => {
             if (res.ok) {
+                loadedConfig = newConfig;
                 statusDiv.textContent = '✓ Saved';
                 statusDiv.style.color = '#4ade80';
-                previewFrame.src = previewFrame.src;
+                loadConfigStatus();
                 setTimeout(() => statusDiv.textContent = '', 2500);
+            } else if (res.status === 422) {
+                res.json().then(({ errors }) => {
+                    const unmatched = showFieldErrors(errors);
+                    statusDiv.textContent = ['✕ Please fix the highlighted fields', ...unmatched].join('\n');
+                    statusDiv.style.color = '#f87171';
+                });
+            } else if (res.status === 401) {
+                statusDiv.textContent = '✕ Not allowed, open Customize from the tray menu';
+                statusDiv.style.color = '#f87171';
             } else {
                 statusDiv.textContent = '✕ Error saving';
                 statusDiv.style.color = '#f87171';
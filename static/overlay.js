@@ -15,6 +15,8 @@ let config = {};
 let currentTheme = '';
 let customStyleEl = null;
 let lastTitle = '';
+let lastArtSrc = '';
+let artPalette = null;      // from the current cover, for use_art_colors
 let transitionAnim = 'slide_up';
 let isVisible = false;     // tracks if overlay is currently shown
 
@@ -27,10 +29,15 @@ fetch('/api/config')
         connectWs();
     });
 
+function applyColors() {
+    const fromArt = config.use_art_colors && artPalette;
+    overlay.style.setProperty('--accent', fromArt ? artPalette.vibrant : config.accent_color);
+    overlay.style.setProperty('--bg', fromArt ? artPalette.dominant : config.background_color);
+    overlay.style.setProperty('--text', fromArt ? artPalette.text : config.text_color);
+}
+
 function applyConfig(cfg) {
-    overlay.style.setProperty('--accent', cfg.accent_color);
-    overlay.style.setProperty('--bg', cfg.background_color);
-    overlay.style.setProperty('--text', cfg.text_color);
+    applyColors();
     overlay.style.setProperty('--font-size', `${cfg.font_size_px}px`);
     overlay.style.setProperty('--radius', `${cfg.border_radius_px ?? 14}px`);
     overlay.style.setProperty('--blur', `${cfg.blur_px ?? 18}px`);
@@ -78,13 +85,33 @@ function connectWs() {
     const ws = new WebSocket(`${protocol}//${window.location.host}/ws`);
 
     ws.onmessage = (event) => {
-        const songInfo = JSON.parse(event.data);
-        updateOverlay(songInfo);
+        const msg = JSON.parse(event.data);
+        switch (msg.kind) {
+            case 'song':
+                handlePlayerEvent(msg.payload);
+                break;
+            case 'config':
+                config = msg.payload;
+                applyConfig(config);
+                break;
+        }
     };
 
     ws.onclose = () => setTimeout(connectWs, 2000);
 }
 
+function handlePlayerEvent(ev) {
+    switch (ev.type) {
+        case 'track_changed':
+            updateOverlay(ev.song);
+            break;
+        case 'stopped':
+        case 'player_gone':
+            hideOverlay();
+            break;
+    }
+}
+
 // ── Helpers ─────────────────────────────────────────
 const fmt = (secs) => {
     const m = Math.floor(secs / 60);
@@ -101,21 +128,37 @@ function triggerSongChange() {
     setTimeout(() => overlay.classList.remove('song-change'), 800);
 }
 
-function setArt(base64) {
-    const src = base64 ? `data:image/png;base64,${base64}` : '';
+function setArt(song) {
+    let src = '';
+    let ambientSrc = '';
+    if (song.album_art_url) {
+        src = song.album_art_url;
+        ambientSrc = song.album_art_background_url || src;
+    } else if (song.album_art_base64) {
+        src = `data:${song.album_art_mime || 'image/png'};base64,${song.album_art_base64}`;
+        ambientSrc = src;
+    }
+    if (src === lastArtSrc) return;
+    lastArtSrc = src;
     albumArt.src = src;
-    ambientArt.src = src;        // ambient blurred background mirrors art
+    ambientArt.src = ambientSrc; // ambient blurred background mirrors art, smaller copy
 }
 
 // ── Update ──────────────────────────────────────────
+function hideOverlay() {
+    if (isVisible) {
+        overlay.classList.add('state-hidden');
+        overlay.classList.remove('playing', 'song-change');
+        isVisible = false;
+    }
+    // replay the entrance if the same track comes back
+    lastTitle = '';
+}
+
 function updateOverlay(song) {
     /* ─ Hide when nothing playing ─ */
     if (!song.title) {
-        if (isVisible) {
-            overlay.classList.add('state-hidden');
-            overlay.classList.remove('playing', 'song-change');
-            isVisible = false;
-        }
+        hideOverlay();
         return;
     }
 
@@ -135,7 +178,6 @@ function updateOverlay(song) {
     if (songChanged) {
         songTitle.textContent = song.title || 'Unknown Title';
         artistName.textContent = song.artist || 'Unknown Artist';
-        setArt(song.album_art_base64);
 
         // Staggered row animations
         triggerSongChange();
@@ -156,6 +198,11 @@ function updateOverlay(song) {
         lastTitle = newTitle;
     }
 
+    /* ─ Art, can change mid-track ─ */
+    setArt(song);
+    artPalette = song.album_art_palette || null;
+    applyColors();
+
     /* ─ Progress ─ */
     currentTimeEl.textContent = fmt(song.position_secs);
     totalTimeEl.textContent = fmt(song.length_secs);